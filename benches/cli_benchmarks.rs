@@ -57,6 +57,7 @@ mod benchmarks {
         let config = Config {
             base_url: "https://api.example.com/v1".to_string(),
             headers: Some(headers),
+            ..Default::default()
         };
 
         let start = Instant::now();
@@ -115,7 +116,7 @@ mod benchmarks {
         let iterations = 1000;
 
         for _ in 0..iterations {
-            let result = parse_anything_schema(black_box(json_str));
+            let result = parse_anything_schema(black_box(json_str)).ok();
             black_box(result);
         }
 
@@ -204,6 +205,7 @@ mod benchmarks {
                 action: black_box("execute".to_string()),
                 content: black_box(Some("echo 'test'".to_string())),
                 error: black_box(Some(false)),
+                ..Default::default()
             };
             black_box(instruction);
         }
@@ -7,6 +7,23 @@ pub struct Cli {
     pub commands: Vec<String>,
 }
 
+impl Cli {
+    /// Parses the arguments keeping a leading `--`, which clap would otherwise
+    /// consume as its own end-of-options marker.
+    pub fn parse_preserving_separator<I, T>(args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+        let mut cli = Self::parse_from(&args);
+        if args.get(1).map(String::as_str) == Some("--") {
+            cli.commands.insert(0, "--".to_string());
+        }
+        cli
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -40,4 +57,16 @@ mod tests {
         let cli = Cli::try_parse_from(["test", "command", "--negative-value", "-123"]).unwrap();
         assert_eq!(cli.commands, vec!["command", "--negative-value", "-123"]);
     }
+
+    #[test]
+    fn test_cli_parsing_keeps_separator() {
+        let cli = Cli::parse_preserving_separator(["test", "users", "--", "-5"]);
+        assert_eq!(cli.commands, vec!["users", "--", "-5"]);
+
+        let cli = Cli::parse_preserving_separator(["test", "--", "-5"]);
+        assert_eq!(cli.commands, vec!["--", "-5"]);
+
+        let cli = Cli::parse_preserving_separator(["test", "--", "--"]);
+        assert_eq!(cli.commands, vec!["--", "--"]);
+    }
 }
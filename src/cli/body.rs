@@ -0,0 +1,413 @@
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::blocking::RequestBuilder;
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum BodyEncoding {
+    #[default]
+    Json,
+    Form,
+    Multipart,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BodyItem {
+    /// `key=value`, or `key=@file` to embed the file's text
+    Field { key: String, value: String },
+    /// `key:=json`, or `key:=@file.json` to embed a JSON file
+    Json { key: String, value: Value },
+    /// `key@path`, a file upload in multipart bodies
+    File { key: String, path: String },
+    /// `@path` or `@-`, the whole body read from a file or stdin
+    Raw { path: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestBody {
+    Json(Value),
+    Form(Vec<(String, String)>),
+    Multipart {
+        fields: Vec<(String, String)>,
+        files: Vec<(String, String)>,
+    },
+    Raw {
+        content: Vec<u8>,
+        content_type: Option<String>,
+    },
+}
+
+fn is_item_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '[' | ']'))
+}
+
+fn read_source(path: &str) -> Result<Vec<u8>, String> {
+    if path == "-" {
+        let mut content = Vec::new();
+        io::stdin()
+            .read_to_end(&mut content)
+            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+        Ok(content)
+    } else {
+        fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+    }
+}
+
+fn read_text(path: &str) -> Result<String, String> {
+    String::from_utf8(read_source(path)?).map_err(|_| format!("{} is not valid UTF-8", path))
+}
+
+/// Parses a single positional argument as a body item. Returns `None` when the
+/// argument is a regular path segment.
+pub fn parse_body_item(arg: &str, encoding: BodyEncoding) -> Result<Option<BodyItem>, String> {
+    if let Some(path) = arg.strip_prefix('@') {
+        if path.is_empty() {
+            return Err("Missing file path after '@'".to_string());
+        }
+        return Ok(Some(BodyItem::Raw {
+            path: path.to_string(),
+        }));
+    }
+
+    if let Some((key, raw)) = arg.split_once(":=") {
+        if is_item_key(key) {
+            let text = match raw.strip_prefix('@') {
+                Some(path) => read_text(path)?,
+                None => raw.to_string(),
+            };
+            let value = serde_json::from_str(&text)
+                .map_err(|e| format!("Invalid JSON for '{}': {}", key, e))?;
+            return Ok(Some(BodyItem::Json {
+                key: key.to_string(),
+                value,
+            }));
+        }
+    }
+
+    if let Some((key, raw)) = arg.split_once('=') {
+        if is_item_key(key) {
+            let value = match raw.strip_prefix('@') {
+                Some(path) => read_text(path)?,
+                None => raw.to_string(),
+            };
+            return Ok(Some(BodyItem::Field {
+                key: key.to_string(),
+                value,
+            }));
+        }
+    }
+
+    // `key@path` only means a file upload in multipart mode, otherwise values
+    // like `users/someone@example.com` would stop being path segments.
+    if encoding == BodyEncoding::Multipart {
+        if let Some((key, path)) = arg.split_once('@') {
+            if is_item_key(key) && !path.is_empty() {
+                return Ok(Some(BodyItem::File {
+                    key: key.to_string(),
+                    path: path.to_string(),
+                }));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Separates body items from path segments, keeping the order of both.
+pub fn split_body_items(
+    args: &[String],
+    encoding: BodyEncoding,
+) -> Result<(Vec<String>, Vec<BodyItem>), String> {
+    let mut segments = Vec::new();
+    let mut items = Vec::new();
+
+    for arg in args {
+        match parse_body_item(arg, encoding)? {
+            Some(item) => items.push(item),
+            None => segments.push(arg.clone()),
+        }
+    }
+
+    Ok((segments, items))
+}
+
+fn content_type_for(path: &str) -> Option<String> {
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Some("application/json".to_string()),
+        Some("xml") => Some("application/xml".to_string()),
+        Some("txt") => Some("text/plain".to_string()),
+        _ => None,
+    }
+}
+
+fn field_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub fn build_body(
+    items: &[BodyItem],
+    encoding: BodyEncoding,
+) -> Result<Option<RequestBody>, String> {
+    if items.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(BodyItem::Raw { path }) = items.iter().find(|i| matches!(i, BodyItem::Raw { .. })) {
+        if items.len() > 1 {
+            return Err("A raw '@file' body can't be combined with other body items".to_string());
+        }
+        return Ok(Some(RequestBody::Raw {
+            content: read_source(path)?,
+            content_type: content_type_for(path),
+        }));
+    }
+
+    match encoding {
+        BodyEncoding::Json => {
+            let mut object = Map::new();
+            for item in items {
+                match item {
+                    BodyItem::Field { key, value } => {
+                        object.insert(key.clone(), Value::String(value.clone()));
+                    }
+                    BodyItem::Json { key, value } => {
+                        object.insert(key.clone(), value.clone());
+                    }
+                    BodyItem::File { key, .. } => {
+                        return Err(format!("File upload '{}' requires --self:multipart", key));
+                    }
+                    BodyItem::Raw { .. } => unreachable!(),
+                }
+            }
+            Ok(Some(RequestBody::Json(Value::Object(object))))
+        }
+        BodyEncoding::Form | BodyEncoding::Multipart => {
+            let mut fields = Vec::new();
+            let mut files = Vec::new();
+            for item in items {
+                match item {
+                    BodyItem::Field { key, value } => fields.push((key.clone(), value.clone())),
+                    BodyItem::Json { key, value } => {
+                        fields.push((key.clone(), field_to_string(value)))
+                    }
+                    BodyItem::File { key, path } => files.push((key.clone(), path.clone())),
+                    BodyItem::Raw { .. } => unreachable!(),
+                }
+            }
+            if encoding == BodyEncoding::Form {
+                Ok(Some(RequestBody::Form(fields)))
+            } else {
+                Ok(Some(RequestBody::Multipart { fields, files }))
+            }
+        }
+    }
+}
+
+/// Reads the request body from stdin when it is piped into the CLI.
+pub fn read_piped_stdin() -> Result<Option<RequestBody>, String> {
+    if io::stdin().is_terminal() {
+        return Ok(None);
+    }
+
+    let content = read_source("-")?;
+    if content.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(None);
+    }
+
+    let content_type = serde_json::from_slice::<Value>(&content)
+        .ok()
+        .map(|_| "application/json".to_string());
+
+    Ok(Some(RequestBody::Raw {
+        content,
+        content_type,
+    }))
+}
+
+pub fn apply_body(request: RequestBuilder, body: RequestBody) -> Result<RequestBuilder, String> {
+    match body {
+        RequestBody::Json(value) => Ok(request.json(&value)),
+        RequestBody::Form(fields) => Ok(request.form(&fields)),
+        RequestBody::Multipart { fields, files } => {
+            let mut form = Form::new();
+            for (key, value) in fields {
+                form = form.text(key, value);
+            }
+            for (key, path) in files {
+                let file_name = Path::new(&path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.clone());
+                let content = read_source(&path)?;
+                form = form.part(key, Part::bytes(content).file_name(file_name));
+            }
+            Ok(request.multipart(form))
+        }
+        RequestBody::Raw {
+            content,
+            content_type,
+        } => {
+            let request = match content_type {
+                Some(content_type) => request.header(CONTENT_TYPE, content_type),
+                None => request,
+            };
+            Ok(request.body(content))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_body_item_field() {
+        let item = parse_body_item("name=John", BodyEncoding::Json).unwrap();
+        assert_eq!(
+            item,
+            Some(BodyItem::Field {
+                key: "name".to_string(),
+                value: "John".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_body_item_json() {
+        let item = parse_body_item("tags:=[\"a\",\"b\"]", BodyEncoding::Json).unwrap();
+        assert_eq!(
+            item,
+            Some(BodyItem::Json {
+                key: "tags".to_string(),
+                value: json!(["a", "b"])
+            })
+        );
+        assert!(parse_body_item("count:=nope", BodyEncoding::Json).is_err());
+    }
+
+    #[test]
+    fn test_parse_body_item_path_segments() {
+        assert_eq!(parse_body_item("users", BodyEncoding::Json).unwrap(), None);
+        assert_eq!(
+            parse_body_item("someone@example.com", BodyEncoding::Json).unwrap(),
+            None
+        );
+        assert_eq!(parse_body_item("a/b=c", BodyEncoding::Json).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_body_item_file_upload_only_in_multipart() {
+        assert_eq!(
+            parse_body_item("avatar@me.png", BodyEncoding::Multipart).unwrap(),
+            Some(BodyItem::File {
+                key: "avatar".to_string(),
+                path: "me.png".to_string()
+            })
+        );
+        assert_eq!(
+            parse_body_item("avatar@me.png", BodyEncoding::Form).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_split_body_items_keeps_segments() {
+        let args = to_args(&["users", "123", "name=John", "age:=30"]);
+        let (segments, items) = split_body_items(&args, BodyEncoding::Json).unwrap();
+        assert_eq!(segments, to_args(&["users", "123"]));
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn test_build_body_json() {
+        let args = to_args(&["name=John", "age:=30", "admin:=true"]);
+        let (_, items) = split_body_items(&args, BodyEncoding::Json).unwrap();
+        let body = build_body(&items, BodyEncoding::Json).unwrap();
+        assert_eq!(
+            body,
+            Some(RequestBody::Json(
+                json!({"name": "John", "age": 30, "admin": true})
+            ))
+        );
+    }
+
+    #[test]
+    fn test_build_body_form() {
+        let args = to_args(&["name=John", "age:=30"]);
+        let (_, items) = split_body_items(&args, BodyEncoding::Form).unwrap();
+        let body = build_body(&items, BodyEncoding::Form).unwrap();
+        assert_eq!(
+            body,
+            Some(RequestBody::Form(vec![
+                ("name".to_string(), "John".to_string()),
+                ("age".to_string(), "30".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_build_body_raw_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("body.json");
+        fs::write(&path, r#"{"hello": "world"}"#).unwrap();
+
+        let items = vec![BodyItem::Raw {
+            path: path.to_string_lossy().into_owned(),
+        }];
+        let body = build_body(&items, BodyEncoding::Json).unwrap().unwrap();
+        match body {
+            RequestBody::Raw {
+                content,
+                content_type,
+            } => {
+                assert_eq!(content, br#"{"hello": "world"}"#);
+                assert_eq!(content_type, Some("application/json".to_string()));
+            }
+            other => panic!("Unexpected body: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_build_body_raw_cannot_mix() {
+        let items = vec![
+            BodyItem::Raw {
+                path: "body.json".to_string(),
+            },
+            BodyItem::Field {
+                key: "a".to_string(),
+                value: "b".to_string(),
+            },
+        ];
+        assert!(build_body(&items, BodyEncoding::Json).is_err());
+    }
+
+    #[test]
+    fn test_build_body_file_requires_multipart() {
+        let items = vec![BodyItem::File {
+            key: "avatar".to_string(),
+            path: "me.png".to_string(),
+        }];
+        assert!(build_body(&items, BodyEncoding::Json).is_err());
+        assert!(build_body(&items, BodyEncoding::Multipart).is_ok());
+    }
+
+    #[test]
+    fn test_build_body_empty() {
+        assert_eq!(build_body(&[], BodyEncoding::Json).unwrap(), None);
+    }
+}
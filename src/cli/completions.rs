@@ -0,0 +1,306 @@
+use crate::cli::help::INTERNAL_COMMANDS;
+use crate::cli::options::{parse_method, SELF_OPTIONS, SELF_OPTION_PREFIX};
+use crate::cli::tokens::{positionals, tokenize};
+use crate::manifest::{Manifest, ParamLocation};
+
+/// Hidden command the completion scripts call with the words typed so far.
+pub const COMPLETE_COMMAND: &str = "self:__complete";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub fn from_name(name: &str) -> Option<Shell> {
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+}
+
+/// Shell function names can't contain dashes or dots in every shell.
+fn function_name(executable_name: &str) -> String {
+    let name: String = executable_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("_{}_complete", name)
+}
+
+pub fn completion_script(shell: Shell, executable_name: &str) -> String {
+    let function = function_name(executable_name);
+    match shell {
+        Shell::Bash => format!(
+            r#"# bash completion for {exe}
+{function}() {{
+    local line="${{COMP_LINE:0:$COMP_POINT}}"
+    local -a words
+    read -ra words <<< "$line"
+    [[ "$line" == *" " ]] && words+=("")
+    local cur="${{words[${{#words[@]}}-1]}}"
+    local IFS=$'\n'
+    local -a candidates=($({exe} {complete} "${{words[@]:1}}" 2>/dev/null))
+    # Bash splits words on ':' and '=', so drop the part it considers typed
+    local prefix="${{cur%"${{COMP_WORDS[COMP_CWORD]}}"}}"
+    COMPREPLY=("${{candidates[@]#"$prefix"}}")
+}}
+complete -o default -F {function} {exe}
+"#,
+            exe = executable_name,
+            function = function,
+            complete = COMPLETE_COMMAND,
+        ),
+        Shell::Zsh => format!(
+            r#"#compdef {exe}
+{function}() {{
+    local output
+    output="$({exe} {complete} "${{(@)words[2,$CURRENT]}}" 2>/dev/null)"
+    if [[ -n "$output" ]]; then
+        compadd -- "${{(@f)output}}"
+    else
+        _files
+    fi
+}}
+compdef {function} {exe}
+"#,
+            exe = executable_name,
+            function = function,
+            complete = COMPLETE_COMMAND,
+        ),
+        Shell::Fish => format!(
+            r#"# fish completion for {exe}
+function {function}
+    set -l tokens (commandline -opc)
+    set -e tokens[1]
+    {exe} {complete} $tokens (commandline -ct) 2>/dev/null
+end
+complete -c {exe} -f -a '({function})'
+"#,
+            exe = executable_name,
+            function = function,
+            complete = COMPLETE_COMMAND,
+        ),
+    }
+}
+
+/// Values of internal commands whose argument is a fixed choice, such as
+/// `refresh|clear`.
+fn internal_choices(command: &str) -> Vec<String> {
+    INTERNAL_COMMANDS
+        .iter()
+        .find(|(name, _, _)| *name == command)
+        .filter(|(_, args, _)| args.contains('|') && !args.contains('<'))
+        .map(|(_, args, _)| args.split('|').map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn manifest_candidates(manifest: &Manifest, words: &[String], current: &str) -> Vec<String> {
+    let mut segments = positionals(&tokenize(words));
+
+    // A leading verb (`post users`) isn't part of the command words
+    if let Some(first) = segments.first() {
+        let is_command_word = manifest
+            .commands
+            .iter()
+            .any(|c| c.segments().first() == Some(&first.as_str()));
+        if parse_method(first).is_some() && !is_command_word {
+            segments.remove(0);
+        }
+    }
+
+    // Completing the value of `--name`
+    if let Some(flag) = words.last().and_then(|w| w.strip_prefix("--")) {
+        if !flag.contains('=') && !current.starts_with('-') {
+            return manifest
+                .commands
+                .iter()
+                .filter(|c| c.match_segments(&segments).is_some())
+                .flat_map(|c| &c.params)
+                .filter(|p| p.name == flag)
+                .flat_map(|p| p.allowed.clone().unwrap_or_default())
+                .collect();
+        }
+    }
+
+    if current.starts_with('-') {
+        return manifest
+            .commands
+            .iter()
+            .filter(|c| c.match_segments(&segments).is_some())
+            .flat_map(|c| &c.params)
+            .filter(|p| p.location != ParamLocation::Path)
+            .map(|p| format!("--{}", p.name))
+            .collect();
+    }
+
+    manifest
+        .commands_under(&segments)
+        .iter()
+        .filter_map(|c| c.segments().get(segments.len()).copied())
+        .filter(|segment| !segment.starts_with('{'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Completion candidates for the last word of `args`, given the words before
+/// it. Candidates not starting with the typed word are left out.
+pub fn complete(args: &[String], manifest: Option<&Manifest>, profiles: &[&str]) -> Vec<String> {
+    let (current, words) = match args.split_last() {
+        Some((current, words)) => (current.as_str(), words),
+        None => ("", &[][..]),
+    };
+
+    let mut candidates: Vec<String> = Vec::new();
+
+    match words.first() {
+        _ if words.last().map(String::as_str) == Some("--self:profile") => {
+            candidates.extend(profiles.iter().map(|p| p.to_string()));
+        }
+        Some(first) if first.starts_with("self:") => match &words[1..] {
+            [] => candidates.extend(internal_choices(first)),
+            [action] if first == "self:profile" && (action == "use" || action == "remove") => {
+                candidates.extend(profiles.iter().map(|p| p.to_string()))
+            }
+            _ => {}
+        },
+        _ => {
+            if words.is_empty() {
+                candidates.extend(
+                    INTERNAL_COMMANDS
+                        .iter()
+                        .map(|(name, _, _)| name.to_string()),
+                );
+            }
+            if current.starts_with(SELF_OPTION_PREFIX) || current == "--" {
+                candidates.extend(
+                    SELF_OPTIONS
+                        .iter()
+                        .map(|option| format!("{}{}", SELF_OPTION_PREFIX, option)),
+                );
+            }
+            if let Some(manifest) = manifest {
+                candidates.extend(manifest_candidates(manifest, words, current));
+            }
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    candidates
+        .into_iter()
+        .filter(|c| c.starts_with(current))
+        .filter(|c| seen.insert(c.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::parse_manifest;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn sample_manifest() -> Manifest {
+        parse_manifest(
+            r#"{
+                "schema": "anything-cli/v0",
+                "commands": [
+                    {"path": "users", "params": [
+                        {"name": "limit", "type": "integer"},
+                        {"name": "status", "enum": ["active", "disabled"]}
+                    ]},
+                    {"path": "users/{id}", "params": [{"name": "id", "in": "path"}]},
+                    {"path": "users/me"},
+                    {"path": "posts", "method": "POST",
+                     "params": [{"name": "title", "in": "body"}]}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_complete_first_word() {
+        let manifest = sample_manifest();
+        let candidates = complete(&to_args(&[""]), Some(&manifest), &[]);
+        assert!(candidates.contains(&"self:set-header".to_string()));
+        assert!(candidates.contains(&"users".to_string()));
+        assert!(candidates.contains(&"posts".to_string()));
+
+        assert_eq!(
+            complete(&to_args(&["self:m"]), None, &[]),
+            to_args(&["self:manifest"])
+        );
+    }
+
+    #[test]
+    fn test_complete_internal_choices() {
+        assert_eq!(
+            complete(&to_args(&["self:manifest", ""]), None, &[]),
+            to_args(&["refresh", "clear"])
+        );
+        assert!(complete(&to_args(&["self:set-header", ""]), None, &[]).is_empty());
+
+        let profiles = ["default", "prod", "staging"];
+        assert_eq!(
+            complete(&to_args(&["self:profile", "use", "s"]), None, &profiles),
+            to_args(&["staging"])
+        );
+        assert_eq!(
+            complete(&to_args(&["users", "--self:profile", "p"]), None, &profiles),
+            to_args(&["prod"])
+        );
+    }
+
+    #[test]
+    fn test_complete_path_segments() {
+        let manifest = sample_manifest();
+        assert_eq!(
+            complete(&to_args(&["users", ""]), Some(&manifest), &[]),
+            to_args(&["me"])
+        );
+        assert_eq!(
+            complete(&to_args(&["post", "p"]), Some(&manifest), &[]),
+            to_args(&["posts"])
+        );
+    }
+
+    #[test]
+    fn test_complete_params_and_values() {
+        let manifest = sample_manifest();
+        let candidates = complete(&to_args(&["users", "--"]), Some(&manifest), &[]);
+        assert_eq!(candidates.len(), SELF_OPTIONS.len() + 2);
+        assert!(candidates.contains(&"--self:method".to_string()));
+        assert!(candidates.ends_with(&to_args(&["--limit", "--status"])));
+        assert_eq!(
+            complete(&to_args(&["posts", "--t"]), Some(&manifest), &[]),
+            to_args(&["--title"])
+        );
+        assert_eq!(
+            complete(&to_args(&["users", "--status", "a"]), Some(&manifest), &[]),
+            to_args(&["active"])
+        );
+    }
+
+    #[test]
+    fn test_completion_scripts_use_executable_name() {
+        let bash = completion_script(Shell::Bash, "my-api");
+        assert!(bash.contains("complete -o default -F _my_api_complete my-api"));
+        assert!(bash.contains("my-api self:__complete"));
+
+        let zsh = completion_script(Shell::Zsh, "my-api");
+        assert!(zsh.starts_with("#compdef my-api"));
+
+        let fish = completion_script(Shell::Fish, "my-api");
+        assert!(fish.contains("complete -c my-api -f -a '(_my_api_complete)'"));
+
+        assert_eq!(Shell::from_name("powershell"), None);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::manifest::{CommandSpec, Manifest, ParamLocation, ParamSpec};
+use reqwest::Method;
+
+/// Built-in commands with their arguments and a short description.
+pub const INTERNAL_COMMANDS: &[(&str, &str, &str)] = &[
+    (
+        "self:set-header",
+        "<KEY> [VALUE]",
+        "Set or remove a request header",
+    ),
+    ("self:set-base-url", "<URL>", "Change the API base URL"),
+    (
+        "self:config",
+        "show [--resolved]",
+        "Show the configuration, or the values in effect and their sources",
+    ),
+    (
+        "self:profile",
+        "list|use|add|remove",
+        "List, switch, add or remove named profiles",
+    ),
+    (
+        "self:trust-key",
+        "[KEY|remove]",
+        "Pin the public key instructions must be signed with",
+    ),
+    ("self:login", "", "Log in with OAuth in the browser"),
+    ("self:logout", "", "Forget the stored OAuth tokens"),
+    (
+        "self:manifest",
+        "refresh|clear",
+        "Refresh or clear the cached server manifest",
+    ),
+    (
+        "self:import-openapi",
+        "<FILE|URL>",
+        "Import commands from an OpenAPI 3 document",
+    ),
+    (
+        "self:validate",
+        "<FILE>",
+        "Check an instructions payload without running it",
+    ),
+    (
+        "self:completions",
+        "bash|zsh|fish",
+        "Print a shell completion script",
+    ),
+    ("self:update", "", "Update to the latest version"),
+    (
+        "self:uninstall",
+        "",
+        "Remove the command and its configuration",
+    ),
+];
+
+fn command_usage(command: &CommandSpec) -> String {
+    let method = command.method();
+    let path = command.segments().join(" ");
+    // Commands with their own words already say what they do
+    if method == Method::GET || command.command.is_some() {
+        path
+    } else {
+        format!("{} {}", method.as_str().to_lowercase(), path)
+    }
+}
+
+fn param_usage(param: &ParamSpec) -> String {
+    let value = match &param.allowed {
+        Some(allowed) => allowed.join("|"),
+        None => param.param_type.name().to_string(),
+    };
+    match param.location {
+        ParamLocation::Query => format!("--{} <{}>", param.name, value),
+        ParamLocation::Path => format!("{{{}}} <{}>", param.name, value),
+        ParamLocation::Body => format!("{}=<{}>", param.name, value),
+    }
+}
+
+fn push_rows(output: &mut String, rows: &[(String, String)]) {
+    let width = rows.iter().map(|(left, _)| left.len()).max().unwrap_or(0);
+    for (left, right) in rows {
+        if right.is_empty() {
+            output.push_str(&format!("  {}\n", left));
+        } else {
+            output.push_str(&format!("  {:width$}  {}\n", left, right, width = width));
+        }
+    }
+}
+
+pub fn render_command_help(executable_name: &str, command: &CommandSpec) -> String {
+    let mut output = format!("Usage: {} {}", executable_name, command_usage(command));
+    if command
+        .params
+        .iter()
+        .any(|p| p.location != ParamLocation::Path)
+    {
+        output.push_str(" [parameters]");
+    }
+    output.push('\n');
+
+    if let Some(description) = &command.description {
+        output.push_str(&format!("\n{}\n", description));
+    }
+
+    if !command.params.is_empty() {
+        output.push_str("\nParameters:\n");
+        let rows: Vec<(String, String)> = command
+            .params
+            .iter()
+            .map(|param| {
+                let mut notes: Vec<String> = Vec::new();
+                if let Some(description) = &param.description {
+                    notes.push(description.clone());
+                }
+                if param.required {
+                    notes.push("(required)".to_string());
+                }
+                if let Some(default) = &param.default {
+                    let default = match default {
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    notes.push(format!("(default: {})", default));
+                }
+                (param_usage(param), notes.join(" "))
+            })
+            .collect();
+        push_rows(&mut output, &rows);
+    }
+
+    output
+}
+
+/// Renders help for the given path: the matching command's details, or the
+/// list of commands under it.
+pub fn render_help(
+    executable_name: &str,
+    manifest: Option<&Manifest>,
+    segments: &[String],
+    method: Option<&Method>,
+) -> String {
+    let mut output = String::new();
+
+    if let Some(manifest) = manifest {
+        if let Some(command) = manifest.find_command(segments, method) {
+            return render_command_help(executable_name, command);
+        }
+
+        output.push_str(&format!(
+            "Usage: {} <command> [--param value]\n",
+            executable_name
+        ));
+
+        if let Some(description) = &manifest.description {
+            output.push_str(&format!("\n{}\n", description));
+        }
+
+        let commands = manifest.commands_under(segments);
+        if !commands.is_empty() {
+            output.push_str("\nCommands:\n");
+            let rows: Vec<(String, String)> = commands
+                .iter()
+                .map(|c| (command_usage(c), c.description.clone().unwrap_or_default()))
+                .collect();
+            push_rows(&mut output, &rows);
+        } else if !segments.is_empty() {
+            output.push_str(&format!(
+                "\nNo commands found under '{}'.\n",
+                segments.join(" ")
+            ));
+        }
+    } else {
+        output.push_str(&format!(
+            "Usage: {} [endpoint/path] [--param value] [--flag]\n",
+            executable_name
+        ));
+        output.push_str("\nThe server doesn't publish a command manifest.\n");
+    }
+
+    output.push_str("\nInternal commands:\n");
+    let rows: Vec<(String, String)> = INTERNAL_COMMANDS
+        .iter()
+        .map(|(name, args, description)| {
+            let usage = if args.is_empty() {
+                name.to_string()
+            } else {
+                format!("{} {}", name, args)
+            };
+            (usage, description.to_string())
+        })
+        .collect();
+    push_rows(&mut output, &rows);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::parse_manifest;
+
+    fn sample_manifest() -> Manifest {
+        parse_manifest(
+            r#"{
+                "schema": "anything-cli/v0",
+                "description": "Example API",
+                "commands": [
+                    {"path": "users", "description": "List users",
+                     "params": [{"name": "limit", "type": "integer", "default": 20,
+                                 "description": "Page size"}]},
+                    {"path": "users", "method": "POST", "description": "Create a user",
+                     "params": [{"name": "name", "in": "body", "required": true}]},
+                    {"path": "posts", "description": "List posts"}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_help_lists_commands() {
+        let manifest = sample_manifest();
+        let help = render_help("my-api", Some(&manifest), &[], None);
+        assert!(help.contains("Usage: my-api <command>"));
+        assert!(help.contains("Example API"));
+        assert!(help.contains("  users       List users\n"));
+        assert!(help.contains("  post users  Create a user\n"));
+        assert!(help.contains("  posts       List posts\n"));
+        assert!(help.contains("self:set-header <KEY> [VALUE]"));
+    }
+
+    #[test]
+    fn test_render_help_for_command() {
+        let manifest = sample_manifest();
+        let help = render_help("my-api", Some(&manifest), &["users".to_string()], None);
+        assert!(help.starts_with("Usage: my-api users [parameters]"));
+        assert!(help.contains("--limit <integer>  Page size (default: 20)"));
+        assert!(!help.contains("Internal commands"));
+    }
+
+    #[test]
+    fn test_render_command_help_required_body() {
+        let manifest = sample_manifest();
+        let help = render_help(
+            "my-api",
+            Some(&manifest),
+            &["users".to_string()],
+            Some(&Method::POST),
+        );
+        assert!(help.starts_with("Usage: my-api post users"));
+        assert!(help.contains("name=<string>  (required)"));
+    }
+
+    #[test]
+    fn test_render_help_without_manifest() {
+        let help = render_help("my-api", None, &[], None);
+        assert!(help.contains("doesn't publish a command manifest"));
+        assert!(help.contains("self:update"));
+    }
+}
@@ -0,0 +1,311 @@
+use crate::cli::body::BodyEncoding;
+use crate::cli::tokens::Token;
+use crate::config::resolve::ConfigOverrides;
+use reqwest::Method;
+
+pub const SELF_OPTION_PREFIX: &str = "--self:";
+
+/// Every `--self:*` option, for completion.
+pub const SELF_OPTIONS: &[&str] = &[
+    "method",
+    "form",
+    "multipart",
+    "ignore-stdin",
+    "profile",
+    "base-url",
+    "header",
+    "answer",
+    "fail-fast",
+    "dry-run",
+    "verbose",
+    "quiet",
+];
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SelfOptions {
+    pub method: Option<Method>,
+    pub body_encoding: BodyEncoding,
+    pub ignore_stdin: bool,
+    pub profile: Option<String>,
+    pub base_url: Option<String>,
+    pub headers: Vec<(String, String)>,
+    /// Answers to server questions, for runs without a terminal.
+    pub answers: Vec<(String, String)>,
+    /// Stop at the first failing instruction.
+    pub fail_fast: bool,
+    /// Print the scripts of the instructions instead of running them.
+    pub dry_run: bool,
+    /// Show `debug` messages.
+    pub verbose: bool,
+    /// Only show errors.
+    pub quiet: bool,
+}
+
+impl SelfOptions {
+    pub fn config_overrides(&self) -> ConfigOverrides {
+        ConfigOverrides {
+            profile: self.profile.clone(),
+            base_url: self.base_url.clone(),
+            headers: self.headers.clone(),
+        }
+    }
+}
+
+fn takes_value(name: &str) -> bool {
+    matches!(
+        name,
+        "method" | "profile" | "base-url" | "header" | "answer"
+    )
+}
+
+fn parse_switch(name: &str, value: Option<String>) -> Result<bool, String> {
+    match value.as_deref() {
+        None | Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(other) => Err(format!("Invalid value for --self:{}: {}", name, other)),
+    }
+}
+
+/// Whether `arg` is a `--self:*` switch that never takes the next argument as its value.
+pub fn is_self_switch(arg: &str) -> bool {
+    arg.strip_prefix(SELF_OPTION_PREFIX)
+        .is_some_and(|option| !option.contains('=') && !takes_value(option))
+}
+
+pub fn parse_method(value: &str) -> Option<Method> {
+    match value.to_uppercase().as_str() {
+        "GET" => Some(Method::GET),
+        "POST" => Some(Method::POST),
+        "PUT" => Some(Method::PUT),
+        "PATCH" => Some(Method::PATCH),
+        "DELETE" => Some(Method::DELETE),
+        "HEAD" => Some(Method::HEAD),
+        "OPTIONS" => Some(Method::OPTIONS),
+        _ => None,
+    }
+}
+
+/// Splits `--self:*` options out of the tokens, returning them parsed
+/// together with the remaining tokens that are meant for the API.
+pub fn extract_self_options(tokens: &[Token]) -> Result<(SelfOptions, Vec<Token>), String> {
+    let mut options = SelfOptions::default();
+    let mut remaining: Vec<Token> = Vec::new();
+
+    for token in tokens {
+        let (name, value) = match token {
+            Token::Long { key, value } if key.starts_with("self:") => {
+                (&key["self:".len()..], value.clone())
+            }
+            _ => {
+                remaining.push(token.clone());
+                continue;
+            }
+        };
+
+        match name {
+            "method" => {
+                let value = value.ok_or("Missing value for --self:method")?;
+                let method = parse_method(&value)
+                    .ok_or_else(|| format!("Unsupported HTTP method: {}", value))?;
+                options.method = Some(method);
+            }
+            "form" => {
+                if parse_switch(name, value)? {
+                    options.body_encoding = BodyEncoding::Form;
+                }
+            }
+            "multipart" => {
+                if parse_switch(name, value)? {
+                    options.body_encoding = BodyEncoding::Multipart;
+                }
+            }
+            "ignore-stdin" => options.ignore_stdin = parse_switch(name, value)?,
+            "fail-fast" => options.fail_fast = parse_switch(name, value)?,
+            "dry-run" => options.dry_run = parse_switch(name, value)?,
+            "verbose" => options.verbose = parse_switch(name, value)?,
+            "quiet" => options.quiet = parse_switch(name, value)?,
+            "profile" => {
+                options.profile = Some(value.ok_or("Missing value for --self:profile")?);
+            }
+            "base-url" => {
+                options.base_url = Some(value.ok_or("Missing value for --self:base-url")?);
+            }
+            "header" => {
+                let value = value.ok_or("Missing value for --self:header")?;
+                let (key, header_value) = value.split_once(':').ok_or_else(|| {
+                    format!("Invalid header '{}', expected \"Key: Value\"", value)
+                })?;
+                options
+                    .headers
+                    .push((key.trim().to_string(), header_value.trim().to_string()));
+            }
+            "answer" => {
+                let value = value.ok_or("Missing value for --self:answer")?;
+                let (name, answer) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid answer '{}', expected name=value", value))?;
+                options
+                    .answers
+                    .push((name.trim().to_string(), answer.to_string()));
+            }
+            _ => return Err(format!("Unknown option: --self:{}", name)),
+        }
+    }
+
+    Ok((options, remaining))
+}
+
+/// Detects a verb-style first argument (`my-api post users`). The verb is only
+/// recognized when at least one path segment follows it, so `my-api delete`
+/// still calls `/delete`.
+pub fn extract_method_verb(commands: &[String]) -> (Option<Method>, &[String]) {
+    if commands.len() > 1 {
+        if let Some(method) = parse_method(&commands[0]) {
+            return (Some(method), &commands[1..]);
+        }
+    }
+    (None, commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::tokens::tokenize;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn to_tokens(args: &[&str]) -> Vec<Token> {
+        tokenize(&to_args(args))
+    }
+
+    #[test]
+    fn test_parse_method_case_insensitive() {
+        assert_eq!(parse_method("post"), Some(Method::POST));
+        assert_eq!(parse_method("Patch"), Some(Method::PATCH));
+        assert_eq!(parse_method("OPTIONS"), Some(Method::OPTIONS));
+        assert_eq!(parse_method("fetch"), None);
+    }
+
+    #[test]
+    fn test_extract_self_options_method_equal() {
+        let tokens = to_tokens(&["--self:method=POST", "--name", "value"]);
+        let (options, remaining) = extract_self_options(&tokens).unwrap();
+        assert_eq!(options.method, Some(Method::POST));
+        assert_eq!(remaining, to_tokens(&["--name", "value"]));
+    }
+
+    #[test]
+    fn test_extract_self_options_method_separate_value() {
+        let tokens = to_tokens(&["--limit", "5", "--self:method", "delete"]);
+        let (options, remaining) = extract_self_options(&tokens).unwrap();
+        assert_eq!(options.method, Some(Method::DELETE));
+        assert_eq!(remaining, to_tokens(&["--limit", "5"]));
+    }
+
+    #[test]
+    fn test_extract_self_options_switches() {
+        let tokens = to_tokens(&[
+            "--self:multipart",
+            "--self:ignore-stdin",
+            "--self:fail-fast",
+            "--self:dry-run",
+            "--self:quiet",
+            "--tag",
+            "a",
+        ]);
+        let (options, remaining) = extract_self_options(&tokens).unwrap();
+        assert_eq!(options.body_encoding, BodyEncoding::Multipart);
+        assert!(options.ignore_stdin);
+        assert!(options.fail_fast);
+        assert!(options.dry_run);
+        assert!(options.quiet && !options.verbose);
+        assert_eq!(remaining, to_tokens(&["--tag", "a"]));
+
+        let tokens = to_tokens(&["--self:form=true"]);
+        let (options, _) = extract_self_options(&tokens).unwrap();
+        assert_eq!(options.body_encoding, BodyEncoding::Form);
+        assert!(extract_self_options(&to_tokens(&["--self:form=yes"])).is_err());
+    }
+
+    #[test]
+    fn test_extract_self_options_profile() {
+        let tokens = to_tokens(&["users", "--self:profile", "prod"]);
+        let (options, remaining) = extract_self_options(&tokens).unwrap();
+        assert_eq!(options.profile.as_deref(), Some("prod"));
+        assert_eq!(remaining, to_tokens(&["users"]));
+    }
+
+    #[test]
+    fn test_extract_self_options_overrides() {
+        let tokens = to_tokens(&[
+            "--self:base-url=http://localhost:3000",
+            "--self:header",
+            "Authorization: Bearer abc",
+            "--self:header=X-Trace:1",
+        ]);
+        let (options, remaining) = extract_self_options(&tokens).unwrap();
+        assert!(remaining.is_empty());
+
+        let overrides = options.config_overrides();
+        assert_eq!(overrides.base_url.as_deref(), Some("http://localhost:3000"));
+        assert_eq!(
+            overrides.headers,
+            vec![
+                ("Authorization".to_string(), "Bearer abc".to_string()),
+                ("X-Trace".to_string(), "1".to_string()),
+            ]
+        );
+        assert!(extract_self_options(&to_tokens(&["--self:header=NoColon"])).is_err());
+    }
+
+    #[test]
+    fn test_extract_self_options_answers() {
+        let tokens = to_tokens(&[
+            "deploy",
+            "--self:answer",
+            "region=eu",
+            "--self:answer=note=a=b",
+        ]);
+        let (options, remaining) = extract_self_options(&tokens).unwrap();
+        assert_eq!(remaining, to_tokens(&["deploy"]));
+        assert_eq!(
+            options.answers,
+            vec![
+                ("region".to_string(), "eu".to_string()),
+                ("note".to_string(), "a=b".to_string()),
+            ]
+        );
+        assert!(extract_self_options(&to_tokens(&["--self:answer=region"])).is_err());
+    }
+
+    #[test]
+    fn test_extract_self_options_errors() {
+        assert!(extract_self_options(&to_tokens(&["--self:method"])).is_err());
+        assert!(extract_self_options(&to_tokens(&["--self:method=FETCH"])).is_err());
+        assert!(extract_self_options(&to_tokens(&["--self:unknown"])).is_err());
+        assert!(extract_self_options(&to_tokens(&["--self:profile"])).is_err());
+    }
+
+    #[test]
+    fn test_extract_method_verb() {
+        let commands = to_args(&["post", "users"]);
+        let (method, rest) = extract_method_verb(&commands);
+        assert_eq!(method, Some(Method::POST));
+        assert_eq!(rest, &to_args(&["users"])[..]);
+    }
+
+    #[test]
+    fn test_extract_method_verb_requires_path() {
+        let commands = to_args(&["delete"]);
+        let (method, rest) = extract_method_verb(&commands);
+        assert!(method.is_none());
+        assert_eq!(rest, &commands[..]);
+
+        let commands = to_args(&["users", "123"]);
+        let (method, rest) = extract_method_verb(&commands);
+        assert!(method.is_none());
+        assert_eq!(rest, &commands[..]);
+    }
+}
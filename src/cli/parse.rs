@@ -1,26 +1,95 @@
-use std::collections::HashMap;
-
-pub fn parse_query_params(args: &[String]) -> HashMap<String, String> {
-    let mut params = HashMap::new();
-    let mut iter = args.iter().peekable();
-
-    while let Some(arg) = iter.next() {
-        if let Some((key, value)) = arg.strip_prefix("--").and_then(|s| s.split_once('=')) {
-            params.insert(key.to_string(), value.to_string());
-        } else if let Some(key) = arg.strip_prefix("--") {
-            if let Some(next_arg) = iter.peek() {
-                if !next_arg.starts_with('-') {
-                    params.insert(key.to_string(), iter.next().unwrap().clone());
-                } else {
-                    params.insert(key.to_string(), "true".to_string());
+use crate::cli::tokens::{tokenize, Token};
+use crate::config::data::ArrayFormat;
+
+/// Query parameters in the order they were given. Repeated keys are kept.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QueryParams(Vec<(String, String)>);
+
+impl QueryParams {
+    pub fn insert(&mut self, key: String, value: String) {
+        self.0.push((key, value));
+    }
+
+    /// Returns the last value given for `key`.
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.0.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<&String> {
+        self.0
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(String, String)> {
+        self.0.iter()
+    }
+
+    /// Builds the final query pairs using the configured array format.
+    pub fn encode(&self, format: ArrayFormat) -> Vec<(String, String)> {
+        let mut encoded: Vec<(String, String)> = Vec::new();
+
+        for (key, value) in &self.0 {
+            let repeated = self.0.iter().filter(|(k, _)| k == key).count() > 1;
+
+            match format {
+                ArrayFormat::Repeat => encoded.push((key.clone(), value.clone())),
+                ArrayFormat::Brackets => {
+                    let mut parts = key.split('.');
+                    let mut name = parts.next().unwrap_or_default().to_string();
+                    for part in parts {
+                        name.push_str(&format!("[{}]", part));
+                    }
+                    if repeated && !name.ends_with("[]") {
+                        name.push_str("[]");
+                    }
+                    encoded.push((name, value.clone()));
+                }
+                ArrayFormat::Comma => {
+                    if let Some((_, joined)) = encoded.iter_mut().find(|(k, _)| k == key) {
+                        joined.push(',');
+                        joined.push_str(value);
+                    } else {
+                        encoded.push((key.clone(), value.clone()));
+                    }
                 }
-            } else {
-                params.insert(key.to_string(), "true".to_string());
             }
-        } else if let Some(flags) = arg.strip_prefix('-') {
-            for flag in flags.chars() {
-                params.insert(flag.to_string(), "true".to_string());
+        }
+
+        encoded
+    }
+}
+
+pub fn parse_query_params(args: &[String]) -> QueryParams {
+    query_params_from_tokens(&tokenize(args))
+}
+
+/// Collects flags into query parameters. A flag without a value is `true`.
+pub fn query_params_from_tokens(tokens: &[Token]) -> QueryParams {
+    let mut params = QueryParams::default();
+
+    for token in tokens {
+        match token {
+            Token::Long { key, value } => {
+                let value = value.clone().unwrap_or_else(|| "true".to_string());
+                params.insert(key.clone(), value);
+            }
+            Token::Short(flags) => {
+                for flag in flags.chars() {
+                    params.insert(flag.to_string(), "true".to_string());
+                }
             }
+            Token::Positional(_) => {}
         }
     }
     params
@@ -100,4 +169,110 @@ mod tests {
         assert_eq!(params.get("kind").unwrap(), "new");
         assert_eq!(params.get("p").unwrap(), "true");
     }
+
+    #[test]
+    fn test_parse_query_params_repeated_keys_keep_order() {
+        let args = vec![
+            "--tag".to_string(),
+            "a".to_string(),
+            "--limit=5".to_string(),
+            "--tag".to_string(),
+            "b".to_string(),
+        ];
+        let params = parse_query_params(&args);
+        assert_eq!(params.len(), 3);
+        assert_eq!(params.get_all("tag"), vec!["a", "b"]);
+        assert_eq!(params.get("tag").unwrap(), "b");
+        assert_eq!(
+            params.encode(ArrayFormat::Repeat),
+            vec![
+                ("tag".to_string(), "a".to_string()),
+                ("limit".to_string(), "5".to_string()),
+                ("tag".to_string(), "b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_query_params_dash_values() {
+        let args = vec![
+            "--since".to_string(),
+            "\\-1d".to_string(),
+            "--offset".to_string(),
+            "-5".to_string(),
+            "--until=-2d".to_string(),
+        ];
+        let params = parse_query_params(&args);
+        assert_eq!(params.get("since").unwrap(), "-1d");
+        assert_eq!(params.get("offset").unwrap(), "-5");
+        assert_eq!(params.get("until").unwrap(), "-2d");
+    }
+
+    #[test]
+    fn test_parse_query_params_ignores_after_separator() {
+        let args = vec![
+            "--limit".to_string(),
+            "5".to_string(),
+            "--".to_string(),
+            "--not-a-param".to_string(),
+        ];
+        let params = parse_query_params(&args);
+        assert_eq!(params.len(), 1);
+        assert_eq!(params.get("limit").unwrap(), "5");
+    }
+
+    #[test]
+    fn test_encode_brackets() {
+        let args = vec![
+            "--tag".to_string(),
+            "a".to_string(),
+            "--tag".to_string(),
+            "b".to_string(),
+            "--filter.status".to_string(),
+            "open".to_string(),
+            "--page".to_string(),
+            "2".to_string(),
+        ];
+        let params = parse_query_params(&args);
+        assert_eq!(
+            params.encode(ArrayFormat::Brackets),
+            vec![
+                ("tag[]".to_string(), "a".to_string()),
+                ("tag[]".to_string(), "b".to_string()),
+                ("filter[status]".to_string(), "open".to_string()),
+                ("page".to_string(), "2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_brackets_explicit_keys() {
+        let args = vec!["--tag[]=a".to_string(), "--filter[status]=open".to_string()];
+        let params = parse_query_params(&args);
+        assert_eq!(
+            params.encode(ArrayFormat::Brackets),
+            vec![
+                ("tag[]".to_string(), "a".to_string()),
+                ("filter[status]".to_string(), "open".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_comma() {
+        let args = vec![
+            "--tag".to_string(),
+            "a".to_string(),
+            "--sort=name".to_string(),
+            "--tag=b".to_string(),
+        ];
+        let params = parse_query_params(&args);
+        assert_eq!(
+            params.encode(ArrayFormat::Comma),
+            vec![
+                ("tag".to_string(), "a,b".to_string()),
+                ("sort".to_string(), "name".to_string()),
+            ]
+        );
+    }
 }
@@ -0,0 +1,221 @@
+use crate::cli::options::is_self_switch;
+
+pub const END_OF_OPTIONS: &str = "--";
+
+/// Flags of internal `self:*` commands. They never take the next argument as
+/// their value, so `self:set-header --secret Authorization ...` keeps the key.
+pub const INTERNAL_SWITCHES: &[&str] = &["secret", "resolved"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    /// A path segment or body item
+    Positional(String),
+    /// `--key value`, `--key=value` or a bare `--flag`
+    Long { key: String, value: Option<String> },
+    /// `-abc`, one or more single character flags
+    Short(String),
+}
+
+fn is_negative_number(arg: &str) -> bool {
+    match arg.strip_prefix('-') {
+        Some(rest) => {
+            rest.chars().any(|c| c.is_ascii_digit())
+                && rest.chars().all(|c| c.is_ascii_digit() || c == '.')
+        }
+        None => false,
+    }
+}
+
+/// Removes the backslash from `\-value`, which is how a value starting with a
+/// dash is written without it being read as a flag.
+fn unescape(arg: &str) -> String {
+    match arg.strip_prefix('\\') {
+        Some(rest) if rest.starts_with('-') => rest.to_string(),
+        _ => arg.to_string(),
+    }
+}
+
+/// Whether `arg` can be the value of the preceding `--key`.
+fn is_value(arg: &str) -> bool {
+    arg != END_OF_OPTIONS && (!arg.starts_with('-') || is_negative_number(arg))
+}
+
+/// Splits raw command-line arguments into positionals and flags.
+///
+/// - Everything after `--` is positional, even if it starts with a dash.
+/// - Negative numbers (`-5`, `-1.5`) are values, never short flags.
+/// - `\-1d` is the literal `-1d`, both as a positional and as a flag value.
+/// - `--key=value` always takes `value`, whatever it starts with.
+pub fn tokenize(args: &[String]) -> Vec<Token> {
+    tokenize_with_switches(args, &[])
+}
+
+/// Like `tokenize`, with `--name` for each of `switches` never taking a value.
+pub fn tokenize_with_switches(args: &[String], switches: &[&str]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut iter = args.iter().peekable();
+
+    while let Some(arg) = iter.next() {
+        if arg == END_OF_OPTIONS {
+            tokens.extend(iter.by_ref().map(|a| Token::Positional(a.clone())));
+            break;
+        }
+
+        if let Some(flag) = arg.strip_prefix("--") {
+            let token = match flag.split_once('=') {
+                Some((key, value)) => Token::Long {
+                    key: key.to_string(),
+                    value: Some(value.to_string()),
+                },
+                None => {
+                    let value = match iter.peek() {
+                        Some(next_arg)
+                            if !is_self_switch(arg)
+                                && !switches.contains(&flag)
+                                && is_value(next_arg) =>
+                        {
+                            iter.next().map(|a| unescape(a))
+                        }
+                        _ => None,
+                    };
+                    Token::Long {
+                        key: flag.to_string(),
+                        value,
+                    }
+                }
+            };
+            tokens.push(token);
+        } else if arg.len() > 1 && arg.starts_with('-') && !is_negative_number(arg) {
+            tokens.push(Token::Short(arg[1..].to_string()));
+        } else {
+            tokens.push(Token::Positional(unescape(arg)));
+        }
+    }
+
+    tokens
+}
+
+pub fn has_flag(tokens: &[Token], name: &str) -> bool {
+    tokens
+        .iter()
+        .any(|token| matches!(token, Token::Long { key, .. } if key == name))
+}
+
+/// Returns the positional arguments, in order.
+pub fn positionals(tokens: &[Token]) -> Vec<String> {
+    tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Positional(value) => Some(value.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn long(key: &str, value: Option<&str>) -> Token {
+        Token::Long {
+            key: key.to_string(),
+            value: value.map(|v| v.to_string()),
+        }
+    }
+
+    fn positional(value: &str) -> Token {
+        Token::Positional(value.to_string())
+    }
+
+    #[test]
+    fn test_tokenize_basic() {
+        let tokens = tokenize(&to_args(&["users", "--limit", "10", "--verbose", "-ab"]));
+        assert_eq!(
+            tokens,
+            vec![
+                positional("users"),
+                long("limit", Some("10")),
+                long("verbose", None),
+                Token::Short("ab".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_equal_value_starting_with_dash() {
+        let tokens = tokenize(&to_args(&["--since=-1d"]));
+        assert_eq!(tokens, vec![long("since", Some("-1d"))]);
+    }
+
+    #[test]
+    fn test_tokenize_negative_numbers() {
+        let tokens = tokenize(&to_args(&["offsets", "-5", "--delta", "-1.5"]));
+        assert_eq!(
+            tokens,
+            vec![
+                positional("offsets"),
+                positional("-5"),
+                long("delta", Some("-1.5")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_escaped_dash() {
+        let tokens = tokenize(&to_args(&["--since", "\\-1d", "\\-x"]));
+        assert_eq!(tokens, vec![long("since", Some("-1d")), positional("-x")]);
+    }
+
+    #[test]
+    fn test_tokenize_end_of_options() {
+        let tokens = tokenize(&to_args(&["--flag", "--", "--not-a-flag", "-v"]));
+        assert_eq!(
+            tokens,
+            vec![
+                long("flag", None),
+                positional("--not-a-flag"),
+                positional("-v"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_flag_does_not_take_separator() {
+        let tokens = tokenize(&to_args(&["--verbose", "--", "users"]));
+        assert_eq!(tokens, vec![long("verbose", None), positional("users")]);
+    }
+
+    #[test]
+    fn test_tokenize_self_switch_does_not_take_value() {
+        let tokens = tokenize(&to_args(&["--self:form", "users", "--self:method", "POST"]));
+        assert_eq!(
+            tokens,
+            vec![
+                long("self:form", None),
+                positional("users"),
+                long("self:method", Some("POST")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_with_switches() {
+        let args = to_args(&["self:set-header", "--secret", "Authorization", "Bearer x"]);
+        let tokens = tokenize_with_switches(&args, INTERNAL_SWITCHES);
+        assert!(has_flag(&tokens, "secret"));
+        assert_eq!(
+            positionals(&tokens),
+            to_args(&["self:set-header", "Authorization", "Bearer x"])
+        );
+    }
+
+    #[test]
+    fn test_positionals() {
+        let tokens = tokenize(&to_args(&["users", "--limit", "5", "123", "-x"]));
+        assert_eq!(positionals(&tokens), to_args(&["users", "123"]));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::cli::completions::{complete, completion_script, Shell};
+use crate::config::data::Config;
+use crate::config::loader::{load_config, load_effective_config};
+use crate::config::resolve::ConfigOverrides;
+use crate::manifest::load_local_manifest;
+use crate::request::apply_default_headers;
+use reqwest::blocking::Client;
+use std::time::Duration;
+
+/// Endpoint, relative to the base URL, asked for candidates when there is no
+/// manifest to complete from.
+pub const COMPLETE_ENDPOINT: &str = ".anything-cli/__complete";
+// Completion runs on every <Tab>, it must never hang the shell
+const COMPLETE_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn handle_completions(executable_name: &str, commands: &[String]) {
+    match commands.get(1).and_then(|name| Shell::from_name(name)) {
+        Some(shell) => print!("{}", completion_script(shell, executable_name)),
+        None => {
+            eprintln!("Usage: {} self:completions bash|zsh|fish", executable_name);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Asks the server for candidates. It receives every typed word as a repeated
+/// `words` query parameter, the last one being the word being completed, and
+/// answers with a JSON array of strings or one candidate per line.
+fn fetch_completions(config: &Config, executable_name: &str, args: &[String]) -> Vec<String> {
+    let url = format!("{}/{}", config.base_url, COMPLETE_ENDPOINT);
+    let query: Vec<(&str, &String)> = args.iter().map(|arg| ("words", arg)).collect();
+    let client = match Client::builder().timeout(COMPLETE_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(_) => return Vec::new(),
+    };
+    let request = apply_default_headers(client.get(&url).query(&query), config, executable_name);
+
+    let text = match request.send() {
+        Ok(response) if response.status().is_success() => response.text().unwrap_or_default(),
+        _ => return Vec::new(),
+    };
+
+    match serde_json::from_str::<Vec<String>>(&text) {
+        Ok(candidates) => candidates,
+        Err(_) => text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// Prints one completion candidate per line for the words typed so far.
+pub fn handle_complete(executable_name: &str, args: &[String]) {
+    let Ok((resolved, config_path)) =
+        load_effective_config(executable_name, &ConfigOverrides::default())
+    else {
+        return;
+    };
+    let config_option = resolved.map(|resolved| resolved.config);
+    let manifest = load_local_manifest(&config_path);
+    let (raw_config, _) = load_config(executable_name);
+    let profiles = raw_config
+        .as_ref()
+        .map(|config| config.profile_names())
+        .unwrap_or_default();
+
+    let mut candidates = complete(args, manifest.as_ref(), &profiles);
+
+    let completes_internal = args.len() > 1 && args[0].starts_with("self:");
+    if manifest.is_none() && !completes_internal {
+        if let Some(config) = &config_option {
+            let current = args.last().map(String::as_str).unwrap_or("");
+            candidates.extend(
+                fetch_completions(config, executable_name, args)
+                    .into_iter()
+                    .filter(|c| c.starts_with(current)),
+            );
+        }
+    }
+
+    for candidate in candidates {
+        println!("{}", candidate);
+    }
+}
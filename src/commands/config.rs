@@ -0,0 +1,147 @@
+use crate::config::loader::{load_config, load_resolved_config};
+use crate::config::resolve::{ConfigOverrides, ResolvedConfig};
+use std::fs;
+
+/// Masks values of headers that usually carry credentials, keeping the last
+/// characters so they can still be told apart.
+fn display_header_value(key: &str, value: &str) -> String {
+    let key = key.to_lowercase();
+    let sensitive = [
+        "authorization",
+        "token",
+        "key",
+        "secret",
+        "cookie",
+        "password",
+    ]
+    .iter()
+    .any(|word| key.contains(word));
+    if !sensitive {
+        return value.to_string();
+    }
+
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", tail)
+}
+
+pub fn render_resolved(resolved: &ResolvedConfig) -> String {
+    let config = &resolved.config;
+    let mut output = String::new();
+
+    output.push_str(&format!(
+        "profile: {}  ({})\n",
+        resolved.profile, resolved.profile_source
+    ));
+    output.push_str(&format!(
+        "base_url: {}  ({})\n",
+        config.base_url, resolved.base_url_source
+    ));
+    let format = serde_json::to_value(config.query_array_format.unwrap_or_default())
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default();
+    output.push_str(&format!(
+        "query_array_format: {}  ({})\n",
+        format, resolved.query_array_format_source
+    ));
+
+    output.push_str("headers:\n");
+    if resolved.header_sources.is_empty() {
+        output.push_str("  (none)\n");
+    }
+    let headers = config.headers.clone().unwrap_or_default();
+    for (key, source) in &resolved.header_sources {
+        let value = headers.get(key).map(String::as_str).unwrap_or_default();
+        output.push_str(&format!(
+            "  {}: {}  ({})\n",
+            key,
+            display_header_value(key, value),
+            source
+        ));
+    }
+
+    output
+}
+
+pub fn handle_config(
+    executable_name: &str,
+    commands: &[String],
+    resolved: bool,
+    overrides: &ConfigOverrides,
+) {
+    if commands.get(1).map(String::as_str) != Some("show") {
+        eprintln!("Usage: {} self:config show [--resolved]", executable_name);
+        std::process::exit(1);
+    }
+
+    if !resolved {
+        let (_, config_path) = load_config(executable_name);
+        match fs::read_to_string(&config_path) {
+            Ok(content) => {
+                println!("# {}", config_path.display());
+                println!("{}", content.trim_end());
+            }
+            Err(_) => println!("No configuration file at {:?}.", config_path),
+        }
+        return;
+    }
+
+    match load_resolved_config(executable_name, overrides) {
+        Ok((Some(resolved), _)) => print!("{}", render_resolved(&resolved)),
+        Ok((None, config_path)) => {
+            println!(
+                "No configuration file at {:?} and no base URL set.",
+                config_path
+            );
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::data::Config;
+    use crate::config::resolve::resolve_config;
+
+    #[test]
+    fn test_display_header_value_masks_credentials() {
+        assert_eq!(
+            display_header_value("Authorization", "Bearer abcdef123456"),
+            "****3456"
+        );
+        assert_eq!(display_header_value("X-API-Key", "short"), "****");
+        assert_eq!(
+            display_header_value("Accept", "application/json"),
+            "application/json"
+        );
+    }
+
+    #[test]
+    fn test_render_resolved_shows_sources() {
+        let config: Config = serde_json::from_str(
+            r#"{"base_url": "http://localhost:3000", "headers": {"Accept": "text/plain"}}"#,
+        )
+        .unwrap();
+        let env = vec![(
+            "MY_API_BASE_URL".to_string(),
+            "https://ci.example.com".to_string(),
+        )];
+        let resolved = resolve_config(Some(&config), "my-api", &ConfigOverrides::default(), &env)
+            .unwrap()
+            .unwrap();
+
+        let output = render_resolved(&resolved);
+        assert!(output.contains("profile: default  (default)"));
+        assert!(output.contains("base_url: https://ci.example.com  (env MY_API_BASE_URL)"));
+        assert!(output.contains("query_array_format: repeat  (default)"));
+        assert!(output.contains("  Accept: text/plain  (config file)"));
+    }
+}
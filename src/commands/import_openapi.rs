@@ -0,0 +1,64 @@
+use crate::config::loader::load_config;
+use crate::manifest::openapi::{manifest_from_openapi, openapi_path, parse_openapi};
+use reqwest::blocking::Client;
+use std::fs;
+
+fn read_source(source: &str) -> Result<String, String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let response = Client::new()
+            .get(source)
+            .send()
+            .map_err(|e| format!("Failed to download {}: {}", source, e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Server returned {} for {}",
+                response.status(),
+                source
+            ));
+        }
+        response
+            .text()
+            .map_err(|e| format!("Failed to read {}: {}", source, e))
+    } else {
+        fs::read_to_string(source).map_err(|e| format!("Failed to read {}: {}", source, e))
+    }
+}
+
+pub fn handle_import_openapi(executable_name: &str, commands: &[String]) {
+    if commands.len() < 2 {
+        eprintln!("Usage: {} self:import-openapi <FILE|URL>", executable_name);
+        std::process::exit(1);
+    }
+
+    let spec = match read_source(&commands[1]).and_then(|content| parse_openapi(&content)) {
+        Ok(spec) => spec,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let manifest = manifest_from_openapi(&spec);
+
+    let (_, config_path) = load_config(executable_name);
+    let path = openapi_path(&config_path);
+    let saved = serde_json::to_string_pretty(&spec)
+        .map_err(|e| e.to_string())
+        .and_then(|data| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::write(&path, data).map_err(|e| e.to_string())
+        });
+
+    if let Err(err) = saved {
+        eprintln!("Failed to save OpenAPI document: {}", err);
+        std::process::exit(1);
+    }
+
+    println!(
+        "Imported {} commands. Run '{} --help' to list them.",
+        manifest.commands.len(),
+        executable_name
+    );
+}
@@ -0,0 +1,84 @@
+use crate::config::loader::load_resolved_config;
+use crate::config::resolve::{ConfigOverrides, ResolvedConfig};
+use crate::manifest::{is_manifest_stale, refresh_manifest};
+use crate::oauth::{
+    clear_tokens, oauth_settings, poll_for_tokens, request_device_authorization, save_tokens,
+};
+use std::path::PathBuf;
+
+fn load(executable_name: &str, overrides: &ConfigOverrides) -> (ResolvedConfig, PathBuf) {
+    match load_resolved_config(executable_name, overrides) {
+        Ok((Some(resolved), config_path)) => (resolved, config_path),
+        Ok((None, config_path)) => {
+            eprintln!(
+                "Failed to load config. Ensure {:?} exists and has the correct format.",
+                config_path
+            );
+            std::process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Logs in with the OAuth device authorization grant and stores the tokens
+/// for the active profile.
+pub fn handle_login(executable_name: &str, overrides: &ConfigOverrides) {
+    let (resolved, config_path) = load(executable_name, overrides);
+    let config = &resolved.config;
+
+    // Servers can announce their OAuth settings in the manifest
+    if config.oauth.is_none() && is_manifest_stale(&config_path) {
+        let _ = refresh_manifest(config, &config_path, executable_name);
+    }
+    let Some(oauth) = oauth_settings(config, &config_path) else {
+        eprintln!("OAuth login is not configured for this CLI.");
+        std::process::exit(1);
+    };
+
+    let device = match request_device_authorization(&oauth, &config.base_url) {
+        Ok(device) => device,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match &device.verification_uri_complete {
+        Some(uri) => println!("Open {} to log in (code: {}).", uri, device.user_code),
+        None => println!(
+            "Open {} and enter the code {}.",
+            device.verification_uri, device.user_code
+        ),
+    }
+    println!("Waiting for approval...");
+
+    let tokens = match poll_for_tokens(&oauth, &config.base_url, &device) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(err) = save_tokens(&config_path, &resolved.profile, &tokens) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    println!("Logged in.");
+}
+
+pub fn handle_logout(executable_name: &str, overrides: &ConfigOverrides) {
+    let (resolved, config_path) = load(executable_name, overrides);
+
+    match clear_tokens(&config_path, &resolved.profile) {
+        Ok(true) => println!("Logged out."),
+        Ok(false) => println!("Not logged in."),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
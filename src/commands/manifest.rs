@@ -0,0 +1,53 @@
+use crate::config::loader::load_effective_config;
+use crate::config::resolve::ConfigOverrides;
+use crate::manifest::{manifest_cache_path, refresh_manifest};
+use std::fs;
+
+pub fn handle_manifest(executable_name: &str, commands: &[String], overrides: &ConfigOverrides) {
+    let (config_option, config_path) = match load_effective_config(executable_name, overrides) {
+        Ok((resolved, config_path)) => (resolved.map(|resolved| resolved.config), config_path),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match commands.get(1).map(String::as_str) {
+        Some("refresh") => {
+            let config = match config_option {
+                Some(config) => config,
+                None => {
+                    eprintln!(
+                        "Failed to load config. Ensure {:?} exists and has the correct format.",
+                        &config_path
+                    );
+                    std::process::exit(1);
+                }
+            };
+
+            match refresh_manifest(&config, &config_path, executable_name) {
+                Ok(manifest) => {
+                    println!("Manifest updated: {} commands.", manifest.commands.len());
+                }
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("clear") => {
+            let cache_path = manifest_cache_path(&config_path);
+            if cache_path.exists() {
+                if let Err(e) = fs::remove_file(&cache_path) {
+                    eprintln!("Failed to delete cached manifest: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            println!("Cached manifest cleared.");
+        }
+        _ => {
+            eprintln!("Usage: {} self:manifest refresh|clear", executable_name);
+            std::process::exit(1);
+        }
+    }
+}
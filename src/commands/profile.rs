@@ -0,0 +1,85 @@
+use crate::config::data::{Profile, DEFAULT_PROFILE};
+use crate::config::loader::{load_config, selected_profile};
+use crate::config::saver::save_config;
+
+fn usage(executable_name: &str) -> ! {
+    eprintln!(
+        "Usage: {} self:profile list | use <NAME> | add <NAME> <URL> | remove <NAME>",
+        executable_name
+    );
+    std::process::exit(1);
+}
+
+pub fn handle_profile(executable_name: &str, commands: &[String], profile: Option<&str>) {
+    let (config_option, config_path) = load_config(executable_name);
+    let mut config = config_option.unwrap_or_default();
+    let name = commands.get(2).map(String::as_str);
+
+    // Message printed once the changed config is saved
+    let message = match (commands.get(1).map(String::as_str), name) {
+        (Some("list"), _) => {
+            let selected = selected_profile(executable_name, profile);
+            let active = config.active_profile(selected.as_deref());
+            let names = config.profile_names();
+            let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+            for name in names {
+                let marker = if name == active { "*" } else { " " };
+                let base_url = config.profile(name).map(|p| p.base_url).unwrap_or_default();
+                println!("{} {:width$}  {}", marker, name, base_url, width = width);
+            }
+            return;
+        }
+        (Some("use"), Some(name)) => {
+            if config.profile(name).is_none() {
+                eprintln!("Unknown profile: {}", name);
+                std::process::exit(1);
+            }
+            config.current_profile = match name {
+                DEFAULT_PROFILE => None,
+                name => Some(name.to_string()),
+            };
+            format!("Switched to profile '{}'.", name)
+        }
+        (Some("add"), Some(name)) => {
+            let Some(base_url) = commands.get(3) else {
+                usage(executable_name);
+            };
+            if config.profile(name).is_some() {
+                eprintln!("Profile '{}' already exists.", name);
+                std::process::exit(1);
+            }
+            config.set_profile(
+                name,
+                Profile {
+                    base_url: base_url.clone(),
+                    ..Default::default()
+                },
+            );
+            format!(
+                "Profile '{}' added. Run '{} self:profile use {}' to switch to it.",
+                name, executable_name, name
+            )
+        }
+        (Some("remove"), Some(name)) => {
+            if name == DEFAULT_PROFILE {
+                eprintln!("The default profile can't be removed.");
+                std::process::exit(1);
+            }
+            if config.profiles.remove(name).is_none() {
+                eprintln!("Unknown profile: {}", name);
+                std::process::exit(1);
+            }
+            if config.current_profile.as_deref() == Some(name) {
+                config.current_profile = None;
+            }
+            format!("Profile '{}' removed.", name)
+        }
+        _ => usage(executable_name),
+    };
+
+    if !save_config(&config, &config_path) {
+        eprintln!("Error while saving the configuration.");
+        std::process::exit(1);
+    }
+    println!("{}", message);
+}
@@ -1,8 +1,8 @@
 use crate::config::data::Config;
-use crate::config::loader::load_config;
+use crate::config::loader::{load_config, selected_profile};
 use crate::config::saver::save_config;
 
-pub fn handle_set_base_url(executable_name: &str, commands: &[String]) {
+pub fn handle_set_base_url(executable_name: &str, commands: &[String], profile: Option<&str>) {
     if commands.len() < 2 {
         eprintln!("Usage: {} set-base-url <URL>", executable_name);
         std::process::exit(1);
@@ -15,9 +15,22 @@ pub fn handle_set_base_url(executable_name: &str, commands: &[String]) {
     let mut config = config_option.unwrap_or_else(|| Config {
         base_url: String::new(),
         headers: None,
+        ..Default::default()
     });
 
-    config.base_url = new_url.clone();
+    // The base URL belongs to the active profile
+    let selected = selected_profile(executable_name, profile);
+    let profile_name = config.active_profile(selected.as_deref()).to_string();
+    let mut profile = match config.profile(&profile_name) {
+        Some(profile) => profile,
+        None => {
+            eprintln!("Unknown profile: {}", profile_name);
+            std::process::exit(1);
+        }
+    };
+
+    profile.base_url = new_url.clone();
+    config.set_profile(&profile_name, profile);
 
     if !save_config(&config, &config_path) {
         eprintln!("Error while saving the configuration.");
@@ -33,7 +46,7 @@ mod tests {
 
     #[test]
     fn test_handle_set_base_url_insufficient_args() {
-        let commands = vec!["self:set-base-url".to_string()];
+        let commands = ["self:set-base-url".to_string()];
         assert!(commands.len() < 2);
     }
 
@@ -45,6 +58,7 @@ mod tests {
         let config = Config {
             base_url: String::new(),
             headers: None,
+            ..Default::default()
         };
 
         assert_eq!(config.base_url, "");
@@ -67,6 +81,7 @@ mod tests {
         let mut config = Config {
             base_url: "https://old-api.example.com".to_string(),
             headers: Some(headers.clone()),
+            ..Default::default()
         };
 
         // Update the base URL
@@ -79,7 +94,7 @@ mod tests {
     #[test]
     fn test_command_parsing() {
         // Test command argument parsing logic
-        let commands = vec![
+        let commands = [
             "self:set-base-url".to_string(),
             "https://api.test.com".to_string(),
         ];
@@ -91,7 +106,7 @@ mod tests {
     #[test]
     fn test_command_parsing_with_extra_args() {
         // Test that extra arguments are ignored
-        let commands = vec![
+        let commands = [
             "self:set-base-url".to_string(),
             "https://api.test.com".to_string(),
             "extra".to_string(),
@@ -1,11 +1,22 @@
 use crate::config::data::Config;
-use crate::config::loader::load_config;
+use crate::config::loader::{load_config, selected_profile};
 use crate::config::saver::save_config;
+use crate::secrets::{secret_name, SecretStore, SECRET_PREFIX};
 use std::collections::HashMap;
 
-pub fn handle_set_header(executable_name: &str, commands: &[String]) {
-    if commands.len() < 2 {
-        eprintln!("Usage: {} set-header <KEY> [VALUE]", executable_name);
+/// Sets or removes a header. With `secret`, the value goes into the encrypted
+/// store and the config only keeps a `secret:` reference to it.
+pub fn handle_set_header(
+    executable_name: &str,
+    commands: &[String],
+    profile: Option<&str>,
+    secret: bool,
+) {
+    if commands.len() < 2 || (secret && commands.len() < 3) {
+        eprintln!(
+            "Usage: {} set-header [--secret] <KEY> [VALUE]",
+            executable_name
+        );
         std::process::exit(1);
     }
 
@@ -17,24 +28,55 @@ pub fn handle_set_header(executable_name: &str, commands: &[String]) {
     let mut config = config_option.unwrap_or_else(|| Config {
         base_url: String::new(),
         headers: Some(HashMap::new()),
+        ..Default::default()
     });
 
-    if config.headers.is_none() {
-        config.headers = Some(HashMap::new());
-    }
-
-    match value {
+    // Headers belong to the active profile
+    let selected = selected_profile(executable_name, profile);
+    let profile_name = config.active_profile(selected.as_deref()).to_string();
+    let mut profile = match config.profile(&profile_name) {
+        Some(profile) => profile,
         None => {
-            if let Some(headers) = &mut config.headers {
-                headers.remove(&key);
-            }
+            eprintln!("Unknown profile: {}", profile_name);
+            std::process::exit(1);
         }
-        Some(v) => {
-            if let Some(headers) = &mut config.headers {
-                headers.insert(key.clone(), v.clone());
-            }
+    };
+
+    let mut store = match SecretStore::open(&config_path) {
+        Ok(store) => store,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
     };
+    let name = secret_name(&profile_name, &key);
+
+    let headers = profile.headers.get_or_insert_with(HashMap::new);
+    let previous = match value {
+        None => headers.remove(&key),
+        Some(v) if secret => {
+            store.set(&name, &v);
+            headers.insert(key.clone(), format!("{}{}", SECRET_PREFIX, name))
+        }
+        Some(v) => headers.insert(key.clone(), v.clone()),
+    };
+    config.set_profile(&profile_name, profile);
+
+    // Drop the stored secret once nothing refers to it anymore
+    let previous_secret = previous
+        .as_deref()
+        .and_then(|v| v.strip_prefix(SECRET_PREFIX))
+        .filter(|previous| !secret || *previous != name);
+    if let Some(previous) = previous_secret {
+        store.remove(previous);
+    }
+
+    if secret || previous_secret.is_some() {
+        if let Err(err) = store.save() {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
 
     if !save_config(&config, &config_path) {
         eprintln!("Error while saving the configuration.");
@@ -50,7 +92,7 @@ mod tests {
     #[test]
     fn test_handle_set_header_insufficient_args() {
         // Test that the function handles insufficient arguments
-        let commands = vec!["self:set-header".to_string()];
+        let commands = ["self:set-header".to_string()];
 
         // The function would call std::process::exit(1) here
         // We test the condition instead
@@ -63,6 +105,7 @@ mod tests {
         let mut config = Config {
             base_url: "https://api.example.com".to_string(),
             headers: Some(HashMap::new()),
+            ..Default::default()
         };
 
         let key = "Authorization".to_string();
@@ -90,6 +133,7 @@ mod tests {
         let mut config = Config {
             base_url: "https://api.example.com".to_string(),
             headers: Some(headers),
+            ..Default::default()
         };
 
         let key = "Authorization".to_string();
@@ -99,7 +143,7 @@ mod tests {
         }
 
         let headers = config.headers.unwrap();
-        assert!(headers.get("Authorization").is_none());
+        assert!(!headers.contains_key("Authorization"));
         assert_eq!(
             headers.get("Content-Type"),
             Some(&"application/json".to_string())
@@ -115,6 +159,7 @@ mod tests {
         let mut config = Config {
             base_url: "https://api.example.com".to_string(),
             headers: Some(headers),
+            ..Default::default()
         };
 
         let key = "Authorization".to_string();
@@ -137,6 +182,7 @@ mod tests {
         let config = Config {
             base_url: String::new(),
             headers: Some(HashMap::new()),
+            ..Default::default()
         };
 
         assert_eq!(config.base_url, "");
@@ -150,6 +196,7 @@ mod tests {
         let mut config = Config {
             base_url: "https://api.example.com".to_string(),
             headers: None,
+            ..Default::default()
         };
 
         if config.headers.is_none() {
@@ -163,7 +210,7 @@ mod tests {
     #[test]
     fn test_command_parsing_key_only() {
         // Test parsing command with key only (for removal)
-        let commands = vec!["self:set-header".to_string(), "Authorization".to_string()];
+        let commands = ["self:set-header".to_string(), "Authorization".to_string()];
 
         assert!(commands.len() >= 2);
         assert_eq!(&commands[1], "Authorization");
@@ -173,7 +220,7 @@ mod tests {
     #[test]
     fn test_command_parsing_key_value() {
         // Test parsing command with key and value
-        let commands = vec![
+        let commands = [
             "self:set-header".to_string(),
             "Authorization".to_string(),
             "Bearer token123".to_string(),
@@ -187,7 +234,7 @@ mod tests {
     #[test]
     fn test_command_parsing_with_spaces_in_value() {
         // Test parsing command with spaces in header value
-        let commands = vec![
+        let commands = [
             "self:set-header".to_string(),
             "User-Agent".to_string(),
             "MyApp/1.0 (Custom Agent)".to_string(),
@@ -207,6 +254,7 @@ mod tests {
         let mut config = Config {
             base_url: "https://api.example.com".to_string(),
             headers: Some(HashMap::new()),
+            ..Default::default()
         };
 
         let headers_to_add = vec![
@@ -0,0 +1,41 @@
+use crate::config::loader::load_config;
+use crate::config::saver::save_config;
+use crate::signing::parse_public_key;
+
+/// Pins the public key instructions must be signed with, removes it, or
+/// shows the pinned one.
+pub fn handle_trust_key(executable_name: &str, commands: &[String]) {
+    let (config_option, config_path) = load_config(executable_name);
+    let mut config = config_option.unwrap_or_default();
+
+    match commands.get(1).map(String::as_str) {
+        None => {
+            match &config.trusted_key {
+                Some(key) => println!("{}", key),
+                None => println!("No key is pinned."),
+            }
+            std::process::exit(0);
+        }
+        Some("remove") => {
+            config.trusted_key = None;
+        }
+        Some(key) => {
+            if let Err(err) = parse_public_key(key) {
+                eprintln!("{}", err);
+                eprintln!("Usage: {} self:trust-key <KEY>|remove", executable_name);
+                std::process::exit(1);
+            }
+            config.trusted_key = Some(key.trim().to_string());
+        }
+    }
+
+    if !save_config(&config, &config_path) {
+        eprintln!("Error while saving the configuration.");
+        std::process::exit(1);
+    }
+    match config.trusted_key {
+        Some(_) => println!("Key pinned. Only instructions signed with it will be processed."),
+        None => println!("Key removed."),
+    }
+    std::process::exit(0);
+}
@@ -1,4 +1,5 @@
 use crate::config::loader::load_config;
+use crate::secrets::store_files;
 use std::fs;
 use std::io::{self, Write};
 use std::process::Command;
@@ -20,6 +21,14 @@ pub fn handle_uninstall(executable_name: &str) {
         println!("  - Config file: {:?}", config_path);
     }
 
+    let secret_files: Vec<_> = store_files(&config_path)
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect();
+    for path in &secret_files {
+        println!("  - Secrets: {:?}", path);
+    }
+
     println!("  - Executable: {:?}", exe_path);
     print!("Are you sure? (y/N): ");
     io::stdout().flush().unwrap();
@@ -33,6 +42,12 @@ pub fn handle_uninstall(executable_name: &str) {
         return;
     }
 
+    for path in &secret_files {
+        if let Err(e) = fs::remove_file(path) {
+            eprintln!("Failed to delete {:?}: {}", path, e);
+        }
+    }
+
     if config_path.exists() {
         if let Err(e) = fs::remove_file(&config_path) {
             eprintln!("Failed to delete config file: {}", e);
@@ -0,0 +1,37 @@
+use crate::schema::validate;
+use std::fs;
+use std::io::{self, Read};
+
+/// Checks an instructions payload without running it, `-` reading it from
+/// stdin.
+pub fn handle_validate(executable_name: &str, commands: &[String]) {
+    let Some(path) = commands.get(1) else {
+        eprintln!("Usage: {} self:validate <FILE>", executable_name);
+        std::process::exit(1);
+    };
+
+    let text = match path.as_str() {
+        "-" => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map(|_| text)
+        }
+        _ => fs::read_to_string(path),
+    };
+    let text = match text {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", path, err);
+            std::process::exit(1);
+        }
+    };
+
+    let errors = validate(&text);
+    if errors.is_empty() {
+        println!("{}: valid", path);
+        std::process::exit(0);
+    }
+    for err in &errors {
+        eprintln!("{}: {}", path, err.message(executable_name));
+    }
+    std::process::exit(1);
+}
@@ -1,10 +1,137 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// Name of the profile stored in the top-level fields of the config.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// How repeated query parameters (`--tag a --tag b`) and dotted object keys
+/// (`--filter.status open`) are encoded in the query string.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArrayFormat {
+    /// `tag=a&tag=b` and `filter.status=open`
+    #[default]
+    Repeat,
+    /// `tag[]=a&tag[]=b` and `filter[status]=open`
+    Brackets,
+    /// `tag=a,b` and `filter.status=open`
+    Comma,
+}
+
+/// OAuth 2.0 device authorization grant settings used by `self:login`.
+/// Endpoints may be relative to the base URL.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct OAuthConfig {
+    pub client_id: String,
+    pub device_authorization_endpoint: String,
+    pub token_endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+/// What `execute` instructions from the server may run.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecuteMode {
+    #[default]
+    Allow,
+    /// Show each command and ask before running it.
+    Confirm,
+    /// Only run commands matching `allow` or `allow_patterns`.
+    Allowlist,
+    Deny,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ExecutePolicy {
+    #[serde(default)]
+    pub mode: ExecuteMode,
+    /// Command prefixes, e.g. `git pull`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    /// Regular expressions matching whole commands.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_patterns: Vec<String>,
+    /// Where commands are logged, `audit.log` next to the config by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<String>,
+}
+
+/// A named environment, such as `staging` or `prod`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Profile {
+    pub base_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_array_format: Option<ArrayFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth: Option<OAuthConfig>,
+}
+
+/// The top-level `base_url`, `headers` and `query_array_format` are the
+/// `default` profile, so configs written before profiles existed still load.
+/// Named profiles are layered on top of them, see `config::resolve`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct Config {
+    #[serde(default)]
     pub base_url: String,
     pub headers: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_array_format: Option<ArrayFormat>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth: Option<OAuthConfig>,
+    /// Environment variables instructions can use as `{{env.NAME}}`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub template_env: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execute: Option<ExecutePolicy>,
+    /// Base64 Ed25519 public key instructions must be signed with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trusted_key: Option<String>,
+}
+
+impl Config {
+    /// The profile to use: the one selected for this invocation, else the
+    /// current one, else `default`.
+    pub fn active_profile<'a>(&'a self, selected: Option<&'a str>) -> &'a str {
+        selected
+            .or(self.current_profile.as_deref())
+            .unwrap_or(DEFAULT_PROFILE)
+    }
+
+    pub fn profile_names(&self) -> Vec<&str> {
+        std::iter::once(DEFAULT_PROFILE)
+            .chain(self.profiles.keys().map(String::as_str))
+            .collect()
+    }
+
+    pub fn profile(&self, name: &str) -> Option<Profile> {
+        if name == DEFAULT_PROFILE {
+            return Some(Profile {
+                base_url: self.base_url.clone(),
+                headers: self.headers.clone(),
+                query_array_format: self.query_array_format,
+                oauth: self.oauth.clone(),
+            });
+        }
+        self.profiles.get(name).cloned()
+    }
+
+    pub fn set_profile(&mut self, name: &str, profile: Profile) {
+        if name == DEFAULT_PROFILE {
+            self.base_url = profile.base_url;
+            self.headers = profile.headers;
+            self.query_array_format = profile.query_array_format;
+            self.oauth = profile.oauth;
+        } else {
+            self.profiles.insert(name.to_string(), profile);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -20,6 +147,7 @@ mod tests {
         let config = Config {
             base_url: "https://api.example.com".to_string(),
             headers: Some(headers),
+            ..Default::default()
         };
 
         let serialized = serde_json::to_string(&config).unwrap();
@@ -33,6 +161,7 @@ mod tests {
         let config = Config {
             base_url: "https://api.example.com".to_string(),
             headers: None,
+            ..Default::default()
         };
 
         let serialized = serde_json::to_string(&config).unwrap();
@@ -69,5 +198,67 @@ mod tests {
 
         assert_eq!(config.base_url, "http://localhost:3000");
         assert!(config.headers.is_none());
+        assert!(config.query_array_format.is_none());
+    }
+
+    #[test]
+    fn test_config_query_array_format() {
+        let json_str = r#"{"base_url": "http://localhost:3000", "query_array_format": "brackets"}"#;
+        let config: Config = serde_json::from_str(json_str).unwrap();
+        assert_eq!(config.query_array_format, Some(ArrayFormat::Brackets));
+
+        let serialized = serde_json::to_string(&Config::default()).unwrap();
+        assert!(!serialized.contains("query_array_format"));
+    }
+
+    #[test]
+    fn test_config_flat_format_is_default_profile() {
+        let json_str = r#"{"base_url": "http://localhost:3000", "headers": {"X-Key": "1"}}"#;
+        let config: Config = serde_json::from_str(json_str).unwrap();
+
+        assert_eq!(config.active_profile(None), DEFAULT_PROFILE);
+        assert_eq!(config.profile_names(), vec![DEFAULT_PROFILE]);
+        assert_eq!(
+            config.profile(DEFAULT_PROFILE).unwrap().base_url,
+            config.base_url
+        );
+
+        let serialized = serde_json::to_string(&config).unwrap();
+        assert!(!serialized.contains("profiles"));
+        assert!(!serialized.contains("current_profile"));
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        let json_str = r#"{
+            "base_url": "http://localhost:3000",
+            "headers": {"X-Key": "local"},
+            "current_profile": "staging",
+            "profiles": {
+                "staging": {"base_url": "https://staging.example.com"},
+                "prod": {"base_url": "https://example.com", "headers": {"X-Key": "prod"}}
+            }
+        }"#;
+        let mut config: Config = serde_json::from_str(json_str).unwrap();
+        assert_eq!(config.profile_names(), vec!["default", "prod", "staging"]);
+        assert_eq!(config.active_profile(None), "staging");
+        assert_eq!(config.active_profile(Some("prod")), "prod");
+        assert!(config.profile("qa").is_none());
+        assert!(config.profile("staging").unwrap().headers.is_none());
+        assert_eq!(
+            config
+                .profile("prod")
+                .unwrap()
+                .headers
+                .unwrap()
+                .get("X-Key"),
+            Some(&"prod".to_string())
+        );
+
+        let mut prod = config.profile("prod").unwrap();
+        prod.base_url = "https://api.example.com".to_string();
+        config.set_profile("prod", prod);
+        assert_eq!(config.profiles["prod"].base_url, "https://api.example.com");
+        assert_eq!(config.base_url, "http://localhost:3000");
     }
 }
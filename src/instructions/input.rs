@@ -0,0 +1,227 @@
+use super::InstructionContext;
+use crate::manifest::ParamType;
+use crate::utils::prompt::{ask, ask_secret};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Content of a `prompt`, `confirm` or `select` instruction. The answer is
+/// stored under `name`, to be sent back with `submit`.
+#[derive(Debug, Deserialize)]
+struct Question {
+    name: String,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    default: Option<Value>,
+    /// `prompt` only: typed without echo.
+    #[serde(default)]
+    secret: bool,
+    /// `prompt` only: regular expression the answer must match.
+    #[serde(default)]
+    pattern: Option<String>,
+    /// `prompt` only: `integer` and `number` answers are sent as numbers.
+    #[serde(default, rename = "type")]
+    value_type: ParamType,
+    /// `prompt` only: whether an empty answer is refused.
+    #[serde(default = "required_by_default")]
+    required: bool,
+    /// `select` only.
+    #[serde(default)]
+    options: Vec<String>,
+}
+
+fn required_by_default() -> bool {
+    true
+}
+
+fn default_text(default: &Option<Value>) -> Option<String> {
+    match default {
+        None | Some(Value::Null) => None,
+        Some(Value::String(text)) => Some(text.clone()),
+        Some(value) => Some(value.to_string()),
+    }
+}
+
+/// Turns a typed answer into the value sent back, applying the default to
+/// empty answers and checking it against the question.
+fn parse_answer(action: &str, question: &Question, raw: &str) -> Result<Value, String> {
+    let raw = raw.trim();
+    let default = default_text(&question.default);
+    let raw = match (raw.is_empty(), &default) {
+        (true, Some(default)) => default.as_str(),
+        _ => raw,
+    };
+
+    match action {
+        "confirm" => match raw.to_lowercase().as_str() {
+            "y" | "yes" | "true" => Ok(Value::Bool(true)),
+            "" | "n" | "no" | "false" => Ok(Value::Bool(false)),
+            _ => Err("Please answer y or n.".to_string()),
+        },
+        "select" => {
+            let chosen = match raw.parse::<usize>() {
+                Ok(index) if index >= 1 && index <= question.options.len() => {
+                    Some(&question.options[index - 1])
+                }
+                _ => question.options.iter().find(|option| *option == raw),
+            };
+            chosen
+                .map(|option| Value::String(option.clone()))
+                .ok_or_else(|| format!("Choose one of: {}", question.options.join(", ")))
+        }
+        _ => {
+            if raw.is_empty() {
+                return match question.required {
+                    true => Err("A value is required.".to_string()),
+                    false => Ok(Value::String(String::new())),
+                };
+            }
+            if !question.value_type.accepts(raw) {
+                return Err(format!("Expected {}.", question.value_type.name()));
+            }
+            if let Some(pattern) = &question.pattern {
+                let regex = Regex::new(pattern)
+                    .map_err(|e| format!("Invalid pattern for {}: {}", question.name, e))?;
+                if !regex.is_match(raw) {
+                    return Err(format!("The value must match {}.", pattern));
+                }
+            }
+            Ok(match question.value_type {
+                ParamType::Integer | ParamType::Number => {
+                    serde_json::from_str(raw).unwrap_or(Value::String(raw.to_string()))
+                }
+                _ => Value::String(raw.to_string()),
+            })
+        }
+    }
+}
+
+/// Asks on the terminal until the answer is valid.
+fn ask_question(action: &str, question: &Question) -> Result<Value, String> {
+    let message = question.message.as_deref().unwrap_or(&question.name);
+    let default = default_text(&question.default);
+
+    if action == "select" {
+        eprintln!("{}", message);
+        for (index, option) in question.options.iter().enumerate() {
+            eprintln!("  {}) {}", index + 1, option);
+        }
+    }
+
+    loop {
+        let raw = match action {
+            "confirm" => {
+                let hint = match parse_answer(action, question, "") {
+                    Ok(Value::Bool(true)) => "Y/n",
+                    _ => "y/N",
+                };
+                ask(&format!("{} [{}]", message, hint), None)?
+            }
+            "select" => ask("Choice", default.as_deref())?,
+            _ if question.secret => ask_secret(message)?,
+            _ => ask(message, default.as_deref())?,
+        };
+
+        match parse_answer(action, question, &raw) {
+            Ok(value) => return Ok(value),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+}
+
+/// Runs a `prompt`, `confirm` or `select` instruction. An answer given with
+/// `--self:answer` is used as is; without a terminal, the default is taken.
+pub fn answer_question(
+    action: &str,
+    content: Option<&str>,
+    context: &mut InstructionContext,
+) -> Result<(), String> {
+    let question: Question = serde_json::from_str(content.unwrap_or_default())
+        .map_err(|e| format!("Invalid {} instruction: {}", action, e))?;
+    if action == "select" && question.options.is_empty() {
+        return Err(format!("No options to select for {}", question.name));
+    }
+
+    let value = match context.preset_answers.get(&question.name) {
+        Some(raw) => parse_answer(action, &question, raw)
+            .map_err(|e| format!("Invalid answer for {}: {}", question.name, e))?,
+        None if context.interactive => ask_question(action, &question)?,
+        None => parse_answer(action, &question, "").map_err(|_| {
+            format!(
+                "No answer for {}. Pass --self:answer {}=<value>.",
+                question.name, question.name
+            )
+        })?,
+    };
+
+    context.answers.insert(question.name, value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn question(content: &str) -> Question {
+        serde_json::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_parse_answer_prompt() {
+        let q = question(r#"{"name": "replicas", "type": "integer", "default": 2}"#);
+        assert_eq!(parse_answer("prompt", &q, "").unwrap(), json!(2));
+        assert_eq!(parse_answer("prompt", &q, "5").unwrap(), json!(5));
+        assert!(parse_answer("prompt", &q, "five").is_err());
+
+        let q = question(r#"{"name": "env", "pattern": "^[a-z]+$"}"#);
+        assert_eq!(
+            parse_answer("prompt", &q, "staging").unwrap(),
+            json!("staging")
+        );
+        assert!(parse_answer("prompt", &q, "Staging!").is_err());
+        assert!(parse_answer("prompt", &q, "").is_err());
+
+        let q = question(r#"{"name": "note", "required": false}"#);
+        assert_eq!(parse_answer("prompt", &q, "").unwrap(), json!(""));
+    }
+
+    #[test]
+    fn test_parse_answer_confirm_and_select() {
+        let q = question(r#"{"name": "go"}"#);
+        assert_eq!(parse_answer("confirm", &q, "").unwrap(), json!(false));
+        assert_eq!(parse_answer("confirm", &q, "Y").unwrap(), json!(true));
+        assert!(parse_answer("confirm", &q, "maybe").is_err());
+
+        let q = question(r#"{"name": "region", "options": ["eu", "us"], "default": "us"}"#);
+        assert_eq!(parse_answer("select", &q, "").unwrap(), json!("us"));
+        assert_eq!(parse_answer("select", &q, "1").unwrap(), json!("eu"));
+        assert_eq!(parse_answer("select", &q, "eu").unwrap(), json!("eu"));
+        assert!(parse_answer("select", &q, "3").is_err());
+    }
+
+    #[test]
+    fn test_answer_question_without_terminal() {
+        let mut context = InstructionContext::default();
+        context
+            .preset_answers
+            .insert("region".to_string(), "2".to_string());
+
+        let select = r#"{"name": "region", "options": ["eu", "us"]}"#;
+        answer_question("select", Some(select), &mut context).unwrap();
+        answer_question(
+            "confirm",
+            Some(r#"{"name": "go", "default": true}"#),
+            &mut context,
+        )
+        .unwrap();
+        assert_eq!(context.answers["region"], json!("us"));
+        assert_eq!(context.answers["go"], json!(true));
+
+        assert_eq!(
+            answer_question("prompt", Some(r#"{"name": "env"}"#), &mut context).unwrap_err(),
+            "No answer for env. Pass --self:answer env=<value>."
+        );
+    }
+}
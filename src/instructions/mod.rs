@@ -1,101 +1,517 @@
-use crate::schema::Instruction;
-use std::process::Command;
+mod auth;
+mod files;
+mod follow_up;
+mod input;
+mod output;
+mod policy;
+mod process;
+mod template;
+
+pub use follow_up::request_key;
+pub use policy::compile_patterns;
+
+use crate::config::data::Config;
+use crate::schema::{parse_anything_schema, Instruction};
+use crate::signing::verify_payload;
+use crate::utils::git::get_git_root;
+use files::FileAction;
+use output::{Level, Spinner, Terminal};
+use process::{run, Script};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// What instructions can use from the invocation that received them.
+#[derive(Debug, Default, Clone)]
+pub struct InstructionContext {
+    pub executable_name: String,
+    /// The effective config, if there is one.
+    pub config: Option<Config>,
+    pub config_path: PathBuf,
+    pub profile: String,
+    /// URL of the request whose response is being processed.
+    pub endpoint: String,
+    /// Nonce sent with that request, when instructions must be signed.
+    pub nonce: Option<String>,
+    /// Whether questions can be asked on the terminal.
+    pub interactive: bool,
+    /// Answers given with `--self:answer`, by question name.
+    pub preset_answers: HashMap<String, String>,
+    /// Answers collected so far, sent back by `submit`.
+    pub answers: Map<String, Value>,
+    /// Keys of the requests that led to the instructions being processed,
+    /// see `request_key`.
+    pub requests: Vec<String>,
+    /// Follow-up requests sent so far, see `MAX_FOLLOW_UP_REQUESTS`.
+    pub follow_ups: usize,
+    /// Values captured from earlier instructions, for `{{name}}` placeholders.
+    pub variables: HashMap<String, String>,
+    /// Exit code of the last script, the `status` variable.
+    pub status: Option<i32>,
+    /// Exit code given by a `stop` instruction, ending the run.
+    pub stopped: Option<i32>,
+    /// Stop at the first failing instruction (`--self:fail-fast`).
+    pub fail_fast: bool,
+    /// Print scripts instead of running them (`--self:dry-run`).
+    pub dry_run: bool,
+    /// Show `debug` messages (`--self:verbose`).
+    pub verbose: bool,
+    /// Only show errors (`--self:quiet`).
+    pub quiet: bool,
+    /// Message of a `spinner`, shown while the next instruction runs.
+    pub spinner: Option<String>,
+    /// Label of the progress bar printed by the previous instruction.
+    pub progress: Option<String>,
+    pub local: template::LocalValues,
+    /// The execute policy's `allow_patterns`, see `compile_patterns`.
+    pub allow_patterns: Vec<Regex>,
+}
+
+/// Handles a response body: instructions are processed, anything else is
+/// printed.
+pub fn process_response(text: &str, context: &mut InstructionContext) -> Result<(), i32> {
+    let parsed = parse_anything_schema(text).map_err(|err| {
+        eprintln!("{}", err.message(&context.executable_name));
+        1
+    })?;
+    if let Some(parsed) = parsed {
+        let trusted_key = context
+            .config
+            .as_ref()
+            .and_then(|config| config.trusted_key.as_deref());
+        if let Some(key) = trusted_key {
+            if let Err(err) = verify_payload(text, key, context.nonce.as_deref()) {
+                eprintln!("Refusing the instructions: {}", err);
+                return Err(1);
+            }
+        }
+        if let Some(instructions) = parsed.instructions {
+            return process_instructions_in(&instructions, context);
+        }
+        println!("{}", text.trim());
+    } else if let Ok(json_val) = serde_json::from_str::<Value>(text) {
+        println!("{}", serde_json::to_string(&json_val).unwrap());
+    } else {
+        println!("{}", text.trim());
+    }
+    Ok(())
+}
 
 pub fn process_instructions(instructions: &[Instruction]) -> Result<(), i32> {
-    let mut has_error = false;
+    process_instructions_in(instructions, &mut InstructionContext::default())
+}
+
+/// A failed instruction.
+#[derive(Debug, PartialEq)]
+struct Failure {
+    /// Exit code for the run.
+    code: i32,
+    /// Set for scripts whose failure the server expects (`error: false` or
+    /// `capture_status`): they only fail the run when it stops there.
+    ignored: bool,
+}
+
+impl Failure {
+    fn error(code: i32) -> Self {
+        Failure {
+            code,
+            ignored: false,
+        }
+    }
+}
+
+/// Runs the instructions in order. By default a failing instruction doesn't
+/// stop the others, and the run exits with the code of the first failure.
+pub fn process_instructions_in(
+    instructions: &[Instruction],
+    context: &mut InstructionContext,
+) -> Result<(), i32> {
+    let mut failure_code = None;
+
+    for (index, instr) in instructions.iter().enumerate() {
+        if context.stopped.is_some() {
+            break;
+        }
+        if let Some(condition) = &instr.condition {
+            match template::evaluate_condition(condition, context) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    failure_code.get_or_insert(1);
+                    continue;
+                }
+            }
+        }
+
+        let spinner = match instr.action.as_str() {
+            "spinner" => None,
+            _ => context.spinner.take().map(Spinner::start),
+        };
+        let result = run_instruction(index + 1, instr, context);
+        if let Some(spinner) = spinner {
+            spinner.stop(result.is_ok());
+        }
+        if instr.action != "progress" {
+            context.progress = None;
+        }
+
+        let Err(failure) = result else {
+            continue;
+        };
+        // A follow-up response was stopped
+        if context.stopped.is_some() {
+            break;
+        }
+        if !failure.ignored {
+            failure_code.get_or_insert(failure.code);
+        }
+        if !instr.on_error.is_empty() {
+            if let Err(code) = process_instructions_in(&instr.on_error, context) {
+                failure_code.get_or_insert(code);
+            }
+        }
+
+        // Later instructions rely on an answer, so don't go on without it
+        let is_question = matches!(instr.action.as_str(), "prompt" | "confirm" | "select");
+        let fail_fast = context.fail_fast && !failure.ignored;
+        if is_question || !instr.continue_on_error.unwrap_or(!fail_fast) {
+            failure_code.get_or_insert(failure.code);
+            break;
+        }
+    }
+
+    match (context.stopped, failure_code) {
+        (Some(0), _) => Ok(()),
+        (Some(code), _) | (None, Some(code)) => Err(code),
+        (None, None) => Ok(()),
+    }
+}
 
-    for instr in instructions {
-        match instr.action.as_str() {
-            "ping" => {
+/// Exit code of an instruction flagged as an error: its `exit_code`, if not 0.
+fn error_code(instr: &Instruction) -> i32 {
+    instr.exit_code.filter(|code| *code != 0).unwrap_or(1)
+}
+
+/// Prints a message of the given level, unless it is filtered out: `debug`
+/// needs `--self:verbose`, and `--self:quiet` keeps only errors.
+fn show(level: Level, text: &str, context: &InstructionContext) {
+    let hidden = match level {
+        Level::Error => false,
+        Level::Debug => context.quiet || !context.verbose,
+        _ => context.quiet,
+    };
+    if hidden {
+        return;
+    }
+    match level.to_stderr() {
+        true => eprintln!("{}", level.format(text, &Terminal::stderr())),
+        false => println!("{}", level.format(text, &Terminal::stdout())),
+    }
+}
+
+/// Applies the execute policy to what an instruction is about to do: `false`
+/// for a dry run, which only shows it.
+fn permitted(number: usize, display: &str, context: &InstructionContext) -> Result<bool, Failure> {
+    policy::permit(display, context).map_err(|reason| {
+        eprintln!(
+            "Instruction {} was not run ({}): {}",
+            number, reason, display
+        );
+        Failure::error(1)
+    })
+}
+
+/// Builds the command of an `execute` instruction: its `command` words,
+/// else its `content` script. Values of the other fields are filled in
+/// like the content.
+fn script_for(
+    instr: &Instruction,
+    content: Option<&str>,
+    context: &mut InstructionContext,
+) -> Result<Option<Script>, String> {
+    let mut script = match (instr.command.is_empty(), content) {
+        (true, None) => return Ok(None),
+        (true, Some(content)) => Script::shell(content),
+        (false, None) => {
+            let command = instr
+                .command
+                .iter()
+                .map(|word| template::render_text(word, context))
+                .collect::<Result<Vec<_>, _>>()?;
+            Script::argv(&command)?
+        }
+        (false, Some(_)) => {
+            return Err("An execute instruction takes a content or a command, not both".to_string())
+        }
+    };
+
+    let base = match instr.repo_root {
+        true => Some(PathBuf::from(
+            get_git_root().ok_or("Not in a git repository, can't run at its root")?,
+        )),
+        false => None,
+    };
+    script.cwd = match &instr.cwd {
+        Some(cwd) => {
+            let cwd = PathBuf::from(template::render_text(cwd, context)?);
+            Some(base.map(|base| base.join(&cwd)).unwrap_or(cwd))
+        }
+        None => base,
+    };
+    for (key, value) in &instr.env {
+        if !is_env_name(key) {
+            return Err(format!("Invalid environment variable name: {:?}", key));
+        }
+        script
+            .env
+            .insert(key.clone(), template::render_text(value, context)?);
+    }
+    if let Some(stdin) = &instr.stdin {
+        script.stdin = Some(template::render_text(stdin, context)?);
+    }
+    script.timeout = instr.timeout_secs.map(Duration::from_secs);
+    script.capture = instr.capture.is_some();
+    Ok(Some(script))
+}
+
+/// `[A-Za-z_][A-Za-z0-9_]*`, so names can't smuggle words into the command
+/// the policy checks.
+fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn run_instruction(
+    number: usize,
+    instr: &Instruction,
+    context: &mut InstructionContext,
+) -> Result<(), Failure> {
+    // Scripts get their values shell-quoted
+    let shell = instr.action == "execute";
+    let content =
+        template::render_content(instr.content.as_deref(), context, shell).map_err(|err| {
+            eprintln!("{}", err);
+            Failure::error(1)
+        })?;
+    let flagged = instr.error.unwrap_or(false);
+
+    match instr.action.as_str() {
+        "ping" => {
+            if !context.quiet {
                 println!("pong");
             }
-            "execute" => {
-                if let Some(script) = &instr.content {
-                    execute_script(script);
-                    if instr.error.unwrap_or(false) {
-                        has_error = true;
+        }
+        "execute" => {
+            let script = script_for(instr, content.as_deref(), context).map_err(|err| {
+                eprintln!("{}", err);
+                Failure::error(1)
+            })?;
+            if let Some(script) = script {
+                let described = script.describe();
+                if !permitted(number, &described, context)? {
+                    return Ok(());
+                }
+
+                let result = run(&script);
+                policy::audit(context, &described, "ran", result.code);
+                context.status = result.code;
+                if let (Some(name), Some(stdout)) = (&instr.capture, result.stdout) {
+                    context.variables.insert(name.clone(), stdout);
+                }
+                if let (Some(name), Some(code)) = (&instr.capture_status, result.code) {
+                    context.variables.insert(name.clone(), code.to_string());
+                }
+
+                if result.code != Some(0) {
+                    let command = script.display.lines().next().unwrap_or_default();
+                    match (result.timed_out, result.code) {
+                        (true, _) => eprintln!(
+                            "Instruction {} timed out after {}s: {}",
+                            number,
+                            instr.timeout_secs.unwrap_or_default(),
+                            command
+                        ),
+                        (false, Some(code)) => eprintln!(
+                            "Instruction {} failed with exit code {}: {}",
+                            number, code, command
+                        ),
+                        (false, None) => eprintln!("Instruction {} failed: {}", number, command),
                     }
+                    return Err(Failure {
+                        code: result.code.unwrap_or(1),
+                        ignored: instr.error == Some(false) || instr.capture_status.is_some(),
+                    });
+                }
+                if flagged {
+                    return Err(Failure::error(error_code(instr)));
                 }
             }
-            "print" => {
-                if let Some(content) = &instr.content {
-                    match instr.error {
-                        Some(true) => {
-                            eprintln!("{}", content);
-                            has_error = true;
-                        }
-                        _ => println!("{}", content),
-                    }
-                } else {
-                    println!();
+        }
+        "write_file" | "download" | "open_url" => {
+            let action = FileAction::parse(&instr.action, content.as_deref()).map_err(|err| {
+                eprintln!("{}", err);
+                Failure::error(1)
+            })?;
+            let display = action.display();
+            if !permitted(number, &display, context)? {
+                return Ok(());
+            }
+            let performed = action.perform(context);
+            let code = if performed.is_ok() { 0 } else { 1 };
+            policy::audit(context, &display, "ran", Some(code));
+            if let Err(err) = performed {
+                eprintln!("{}", err);
+                return Err(Failure::error(1));
+            }
+        }
+        "table" | "kv" | "markdown" | "progress" | "spinner" if context.quiet => {}
+        "table" | "kv" | "markdown" => {
+            let terminal = Terminal::stdout();
+            let content = content.unwrap_or_default();
+            let rendered = match instr.action.as_str() {
+                "table" => output::table(&content, &terminal),
+                "kv" => output::kv(&content, &terminal),
+                _ => Ok(output::markdown(&content, &terminal)),
+            };
+            match rendered {
+                Ok(rendered) => println!("{}", rendered),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return Err(Failure::error(1));
                 }
             }
-            "none" => {
-                if instr.error.unwrap_or(false) {
-                    has_error = true;
+        }
+        "progress" => {
+            let terminal = Terminal::stdout();
+            let (label, line) = output::progress(content.as_deref().unwrap_or_default(), &terminal)
+                .map_err(|err| {
+                    eprintln!("{}", err);
+                    Failure::error(1)
+                })?;
+            // Consecutive updates of the same bar replace each other
+            if terminal.styled && context.progress.as_ref() == Some(&label) {
+                print!("{}", output::CLEAR_PREVIOUS_LINE);
+            }
+            println!("{}", line);
+            context.progress = Some(label);
+        }
+        "spinner" => {
+            context.spinner = content;
+        }
+        "print" | "warn" | "success" => {
+            let level = match instr.action.as_str() {
+                "warn" => Some(Level::Warn),
+                "success" => Some(Level::Success),
+                _ => instr
+                    .level
+                    .as_deref()
+                    .map(Level::parse)
+                    .transpose()
+                    .map_err(|err| {
+                        eprintln!("{}", err);
+                        Failure::error(1)
+                    })?,
+            };
+            match (level, &content, flagged) {
+                // Only the `error` level fails the run, whatever the `error` field says
+                (Some(level), content, _) => {
+                    show(level, content.as_deref().unwrap_or_default(), context);
+                    if level == Level::Error {
+                        return Err(Failure::error(error_code(instr)));
+                    }
+                }
+                (None, Some(content), true) => {
+                    eprintln!("{}", content);
+                    return Err(Failure::error(error_code(instr)));
                 }
+                (None, _, false) if context.quiet => {}
+                (None, Some(content), false) => println!("{}", content),
+                (None, None, _) => println!(),
             }
-            _ => {
-                eprintln!("Unsupported action: {}", instr.action);
+        }
+        "auth" => {
+            let content = content.as_deref();
+            if let Err(err) = auth::authenticate(content, context, &mut auth::ask_field) {
+                eprintln!("{}", err);
+                return Err(Failure::error(1));
             }
         }
-    }
-
-    if has_error {
-        Err(1)
-    } else {
-        Ok(())
-    }
-}
-
-fn execute_script(script: &str) {
-    let status = Command::new("sh").arg("-c").arg(script).status();
-
-    match status {
-        Ok(exit_status) => {
-            if !exit_status.success() {
-                eprintln!("Error: {:?}", exit_status);
+        "prompt" | "confirm" | "select" => {
+            let content = content.as_deref();
+            if let Err(err) = input::answer_question(&instr.action, content, context) {
+                eprintln!("{}", err);
+                return Err(Failure::error(1));
+            }
+        }
+        "request" => {
+            follow_up::request(content.as_deref(), context).map_err(Failure::error)?;
+        }
+        "submit" => {
+            follow_up::submit(content.as_deref(), context).map_err(Failure::error)?;
+        }
+        "stop" => {
+            let code = instr.exit_code.unwrap_or(0);
+            if let Some(content) = &content {
+                match code {
+                    0 if context.quiet => {}
+                    0 => println!("{}", content),
+                    _ => eprintln!("{}", content),
+                }
+            }
+            context.stopped = Some(code);
+        }
+        "none" => {
+            if flagged || instr.exit_code.is_some_and(|code| code != 0) {
+                return Err(Failure::error(error_code(instr)));
             }
         }
-        Err(err) => {
-            eprintln!("Error: {}", err);
+        _ => {
+            eprintln!("Unsupported action: {}", instr.action);
         }
     }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::data::{ExecuteMode, ExecutePolicy};
 
     #[test]
     fn test_execute_script_echo() {
         // This test executes a simple echo command
-        // Since execute_script doesn't return a value, we're mainly testing it doesn't panic
-        execute_script("echo 'test'");
+        // Mainly testing that it doesn't panic
+        run(&Script::shell("echo 'test'"));
     }
 
     #[test]
     fn test_execute_script_true() {
-        execute_script("true");
+        run(&Script::shell("true"));
     }
 
     #[test]
     fn test_execute_script_false() {
         // This should print an error but not panic
-        execute_script("false");
+        run(&Script::shell("false"));
     }
 
     #[test]
     fn test_execute_script_invalid_command() {
         // Test with an invalid command
         // This should print an error but not panic
-        execute_script("nonexistent_command_12345");
+        run(&Script::shell("nonexistent_command_12345"));
     }
 
     #[test]
     fn test_execute_script_complex() {
         // Test with a more complex script
-        execute_script("echo 'hello' && echo 'world'");
+        run(&Script::shell("echo 'hello' && echo 'world'"));
     }
 
     #[test]
@@ -105,18 +521,21 @@ mod tests {
             action: "ping".to_string(),
             content: None,
             error: None,
+            ..Default::default()
         };
 
         let print_instruction = Instruction {
             action: "print".to_string(),
             content: Some("Hello, World!".to_string()),
             error: Some(false),
+            ..Default::default()
         };
 
         let execute_instruction = Instruction {
             action: "execute".to_string(),
             content: Some("echo 'test'".to_string()),
             error: Some(true),
+            ..Default::default()
         };
 
         assert_eq!(ping_instruction.action, "ping");
@@ -139,21 +558,25 @@ mod tests {
                 action: "ping".to_string(),
                 content: None,
                 error: None,
+                ..Default::default()
             },
             Instruction {
                 action: "print".to_string(),
                 content: Some("This should print".to_string()),
                 error: Some(false),
+                ..Default::default()
             },
             Instruction {
                 action: "execute".to_string(),
                 content: Some("echo 'test'".to_string()),
                 error: Some(true), // This should cause an error
+                ..Default::default()
             },
             Instruction {
                 action: "print".to_string(),
                 content: Some("This should still print despite previous error".to_string()),
                 error: Some(false),
+                ..Default::default()
             },
         ];
 
@@ -171,11 +594,13 @@ mod tests {
                 action: "print".to_string(),
                 content: Some("This is an error message".to_string()),
                 error: Some(true), // This should cause an error
+                ..Default::default()
             },
             Instruction {
                 action: "print".to_string(),
                 content: Some("This should still print".to_string()),
                 error: Some(false),
+                ..Default::default()
             },
         ];
 
@@ -193,16 +618,19 @@ mod tests {
                 action: "ping".to_string(),
                 content: None,
                 error: None,
+                ..Default::default()
             },
             Instruction {
                 action: "print".to_string(),
                 content: Some("Hello".to_string()),
                 error: Some(false),
+                ..Default::default()
             },
             Instruction {
                 action: "execute".to_string(),
                 content: Some("echo 'test'".to_string()),
                 error: Some(false),
+                ..Default::default()
             },
         ];
 
@@ -218,26 +646,31 @@ mod tests {
                 action: "ping".to_string(),
                 content: None,
                 error: None,
+                ..Default::default()
             },
             Instruction {
                 action: "print".to_string(),
                 content: None, // No content - should print empty line, not fail
                 error: Some(false),
+                ..Default::default()
             },
             Instruction {
                 action: "execute".to_string(),
                 content: None, // No content - should be allowed (no-op), not fail
                 error: Some(false),
+                ..Default::default()
             },
             Instruction {
                 action: "unknown_action".to_string(), // Unsupported action - should warn, not fail
                 content: Some("test".to_string()),
                 error: Some(false),
+                ..Default::default()
             },
             Instruction {
                 action: "print".to_string(),
                 content: Some("This should still work".to_string()),
                 error: Some(false),
+                ..Default::default()
             },
         ];
 
@@ -253,6 +686,7 @@ mod tests {
             action: "execute".to_string(),
             content: None, // Missing content for execute is now allowed
             error: Some(false),
+            ..Default::default()
         }];
 
         let result = process_instructions(&instructions);
@@ -266,10 +700,269 @@ mod tests {
             action: "none".to_string(),
             content: None,
             error: Some(true), // Should cause error
+            ..Default::default()
         }];
 
         let result = process_instructions(&instructions);
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), 1);
     }
+
+    #[test]
+    fn test_capture_and_interpolation() {
+        let instructions = vec![
+            Instruction {
+                action: "execute".to_string(),
+                content: Some("echo 1.2.0".to_string()),
+                capture: Some("version".to_string()),
+                ..Default::default()
+            },
+            Instruction {
+                action: "execute".to_string(),
+                content: Some("exit 3".to_string()),
+                capture_status: Some("status".to_string()),
+                ..Default::default()
+            },
+            Instruction {
+                action: "execute".to_string(),
+                content: Some("test {{version}} = 1.2.0 && test {{status}} = 3".to_string()),
+                capture_status: Some("check".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let mut context = InstructionContext::default();
+        assert!(process_instructions_in(&instructions, &mut context).is_ok());
+        assert_eq!(context.variables["version"], "1.2.0");
+        assert_eq!(context.variables["status"], "3");
+        assert_eq!(context.variables["check"], "0");
+
+        let unknown = vec![Instruction {
+            action: "print".to_string(),
+            content: Some("{{missing}}".to_string()),
+            ..Default::default()
+        }];
+        assert!(process_instructions_in(&unknown, &mut context).is_err());
+    }
+
+    #[test]
+    fn test_conditions_and_error_handlers() {
+        let instructions: Vec<Instruction> = serde_json::from_str(
+            r#"[
+                {"action": "execute", "content": "exit 3", "capture_status": "code",
+                 "on_error": [{"action": "execute", "content": "echo cleaned", "capture": "cleanup"}]},
+                {"action": "execute", "content": "echo skipped", "capture": "skipped", "if": "code == 0"},
+                {"action": "stop", "content": "Tests failed", "exit_code": 4, "if": "code != 0"},
+                {"action": "execute", "content": "echo after", "capture": "after"}
+            ]"#,
+        )
+        .unwrap();
+
+        let mut context = InstructionContext::default();
+        assert_eq!(process_instructions_in(&instructions, &mut context), Err(4));
+        assert_eq!(context.variables["cleanup"], "cleaned");
+        assert!(!context.variables.contains_key("skipped"));
+        assert!(!context.variables.contains_key("after"));
+    }
+
+    #[test]
+    fn test_message_levels() {
+        let warnings: Vec<Instruction> = serde_json::from_str(
+            r#"[
+                {"action": "warn", "content": "Disk almost full", "error": true},
+                {"action": "success", "content": "Deployed"},
+                {"action": "print", "content": "Details", "level": "debug"}
+            ]"#,
+        )
+        .unwrap();
+        assert!(process_instructions(&warnings).is_ok());
+
+        let errors: Vec<Instruction> = serde_json::from_str(
+            r#"[
+                {"action": "print", "content": "Quota exceeded", "level": "error", "exit_code": 3},
+                {"action": "print", "content": "x", "level": "fatal"}
+            ]"#,
+        )
+        .unwrap();
+        let mut context = InstructionContext {
+            quiet: true,
+            ..Default::default()
+        };
+        assert_eq!(process_instructions_in(&errors, &mut context), Err(3));
+        assert_eq!(process_instructions_in(&errors[1..], &mut context), Err(1));
+    }
+
+    #[test]
+    fn test_continue_on_error_false() {
+        let instructions: Vec<Instruction> = serde_json::from_str(
+            r#"[
+                {"action": "execute", "content": "false"},
+                {"action": "execute", "content": "false", "continue_on_error": false},
+                {"action": "execute", "content": "echo after", "capture": "after"}
+            ]"#,
+        )
+        .unwrap();
+
+        let mut context = InstructionContext::default();
+        assert_eq!(process_instructions_in(&instructions, &mut context), Err(1));
+        assert!(!context.variables.contains_key("after"));
+
+        let stopped: Vec<Instruction> = serde_json::from_str(
+            r#"[{"action": "stop"}, {"action": "print", "content": "x", "error": true}]"#,
+        )
+        .unwrap();
+        assert!(process_instructions(&stopped).is_ok());
+    }
+
+    #[test]
+    fn test_script_exit_codes() {
+        let instructions: Vec<Instruction> = serde_json::from_str(
+            r#"[
+                {"action": "execute", "content": "exit 2", "error": false},
+                {"action": "execute", "content": "exit 3", "capture_status": "code"},
+                {"action": "execute", "content": "exit 4"},
+                {"action": "execute", "content": "exit 5"},
+                {"action": "execute", "content": "echo after", "capture": "after"}
+            ]"#,
+        )
+        .unwrap();
+
+        let mut context = InstructionContext::default();
+        assert_eq!(process_instructions_in(&instructions, &mut context), Err(4));
+        assert_eq!(context.variables["after"], "after");
+
+        let mut context = InstructionContext {
+            fail_fast: true,
+            ..Default::default()
+        };
+        assert_eq!(process_instructions_in(&instructions, &mut context), Err(4));
+        assert!(!context.variables.contains_key("after"));
+
+        let none: Vec<Instruction> =
+            serde_json::from_str(r#"[{"action": "none", "exit_code": 7}]"#).unwrap();
+        assert_eq!(process_instructions(&none), Err(7));
+    }
+
+    #[test]
+    fn test_unsigned_instructions_refused_with_pinned_key() {
+        let mut context = InstructionContext {
+            config: Some(Config {
+                // Public key of the all-zero test seed
+                trusted_key: Some("O2onvM62pC1io6jQKm8Nc2UyFXcd4kOmOsBIoYtZ2ik=".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let body = r#"{"schema": "anything-cli/v0", "instructions": [
+            {"action": "execute", "content": "echo ran", "capture": "ran"}]}"#;
+
+        assert_eq!(process_response(body, &mut context), Err(1));
+        assert!(context.variables.is_empty());
+    }
+
+    #[test]
+    fn test_response_with_unknown_action_runs_the_rest() {
+        // A newer minor version of the schema may add actions
+        let mut context = InstructionContext::default();
+        let body = r#"{"schema": "anything-cli/v0.9", "instructions": [
+            {"action": "sparkle"},
+            {"action": "execute", "content": "echo ran", "capture": "ran"}]}"#;
+
+        assert_eq!(process_response(body, &mut context), Ok(()));
+        assert_eq!(context.variables["ran"], "ran");
+    }
+
+    #[test]
+    fn test_structured_execute() {
+        let instructions: Vec<Instruction> = serde_json::from_str(
+            r#"[
+                {"action": "execute", "command": ["printf", "%s", "{{name}}"], "capture": "echoed"},
+                {"action": "execute", "command": ["sh", "-c", "cat; echo \" $TARGET\""],
+                 "stdin": "deploying", "env": {"TARGET": "{{name}}"}, "capture": "piped"},
+                {"action": "execute", "command": ["pwd"], "cwd": "/", "capture": "dir"}
+            ]"#,
+        )
+        .unwrap();
+
+        let mut context = InstructionContext::default();
+        context
+            .variables
+            .insert("name".to_string(), "it's; fine".to_string());
+        assert!(process_instructions_in(&instructions, &mut context).is_ok());
+        assert_eq!(context.variables["echoed"], "it's; fine");
+        assert_eq!(context.variables["piped"], "deploying it's; fine");
+        assert_eq!(context.variables["dir"], "/");
+
+        let both: Vec<Instruction> =
+            serde_json::from_str(r#"[{"action": "execute", "content": "ls", "command": ["ls"]}]"#)
+                .unwrap();
+        assert_eq!(process_instructions(&both), Err(1));
+    }
+
+    #[test]
+    fn test_allowlist_sees_env_cwd_and_stdin() {
+        let mut context = InstructionContext {
+            config: Some(Config {
+                execute: Some(ExecutePolicy {
+                    mode: ExecuteMode::Allowlist,
+                    allow: vec!["printf ok".to_string()],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let allowed: Vec<Instruction> = serde_json::from_str(
+            r#"[{"action": "execute", "command": ["printf", "ok"], "capture": "out"}]"#,
+        )
+        .unwrap();
+        assert!(process_instructions_in(&allowed, &mut context).is_ok());
+        assert_eq!(context.variables["out"], "ok");
+
+        for extra in [
+            r#""env": {"LD_PRELOAD": "/tmp/evil.so"}"#,
+            r#""cwd": "/tmp""#,
+            r#""stdin": "input""#,
+        ] {
+            let injected: Vec<Instruction> = serde_json::from_str(&format!(
+                r#"[{{"action": "execute", "command": ["printf", "ok"], "capture": "injected", {}}}]"#,
+                extra
+            ))
+            .unwrap();
+            assert_eq!(process_instructions_in(&injected, &mut context), Err(1));
+            assert!(!context.variables.contains_key("injected"));
+        }
+    }
+
+    #[test]
+    fn test_execute_refuses_invalid_env_names() {
+        let mut context = InstructionContext {
+            config: Some(Config {
+                execute: Some(ExecutePolicy {
+                    mode: ExecuteMode::Allowlist,
+                    allow: vec!["git pull".to_string()],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        for name in ["git pull x", "", "1X", "A=B", "X;Y"] {
+            let instructions: Vec<Instruction> = serde_json::from_str(&format!(
+                r#"[{{"action": "execute", "command": ["printf", "ran"], "capture": "out", "env": {{{:?}: "1"}}}}]"#,
+                name
+            ))
+            .unwrap();
+            assert_eq!(
+                script_for(&instructions[0], None, &mut context).unwrap_err(),
+                format!("Invalid environment variable name: {:?}", name)
+            );
+            assert_eq!(process_instructions_in(&instructions, &mut context), Err(1));
+            assert!(!context.variables.contains_key("out"));
+        }
+
+        assert!(is_env_name("_PATH_2"));
+    }
 }
@@ -0,0 +1,532 @@
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::Value;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Erases the previous line, to redraw a progress bar in place.
+pub const CLEAR_PREVIOUS_LINE: &str = "\x1b[1A\x1b[2K";
+
+const SPINNER_FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How output can be rendered. Without styling, everything is plain text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Terminal {
+    /// Colors and redrawing: the output is a terminal and `NO_COLOR` is unset.
+    pub styled: bool,
+    /// Columns available, when the output is a terminal.
+    pub width: Option<usize>,
+}
+
+#[cfg(unix)]
+fn window_width(fd: i32) -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+#[cfg(not(unix))]
+fn window_width(_fd: i32) -> Option<usize> {
+    None
+}
+
+impl Terminal {
+    fn detect(is_terminal: bool, fd: i32) -> Self {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let width = is_terminal.then(|| {
+            window_width(fd)
+                .or_else(|| env::var("COLUMNS").ok()?.parse().ok())
+                .unwrap_or(80)
+        });
+        Terminal {
+            styled: is_terminal && !no_color,
+            width,
+        }
+    }
+
+    pub fn stdout() -> Self {
+        Terminal::detect(io::stdout().is_terminal(), 1)
+    }
+
+    pub fn stderr() -> Self {
+        Terminal::detect(io::stderr().is_terminal(), 2)
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        match self.styled {
+            true => format!("{}{}{}", style, text, RESET),
+            false => text.to_string(),
+        }
+    }
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(width.saturating_sub(1)).collect();
+    format!("{}…", kept)
+}
+
+fn pad(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.chars().count());
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+/// Content of a `table` instruction. Rows are arrays in column order, or
+/// objects keyed by column name.
+#[derive(Debug, Deserialize)]
+struct TableContent {
+    columns: Vec<String>,
+    #[serde(default)]
+    rows: Vec<Value>,
+    /// Widest a column may be before its values are cut.
+    #[serde(default)]
+    max_width: Option<usize>,
+}
+
+pub fn table(content: &str, terminal: &Terminal) -> Result<String, String> {
+    let content: TableContent =
+        serde_json::from_str(content).map_err(|e| format!("Invalid table instruction: {}", e))?;
+    let columns = &content.columns;
+    let rows: Vec<Vec<String>> = content
+        .rows
+        .iter()
+        .map(|row| match row {
+            Value::Array(cells) => (0..columns.len())
+                .map(|index| cells.get(index).map(text_of).unwrap_or_default())
+                .collect(),
+            Value::Object(cells) => columns
+                .iter()
+                .map(|column| cells.get(column).map(text_of).unwrap_or_default())
+                .collect(),
+            other => vec![text_of(other)],
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            rows.iter()
+                .filter_map(|row| row.get(index))
+                .chain(std::iter::once(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+                .min(content.max_width.unwrap_or(usize::MAX))
+        })
+        .collect();
+
+    // Narrow the widest columns until the table fits the terminal
+    if let Some(width) = terminal.width {
+        let available = width.saturating_sub(2 * columns.len().saturating_sub(1));
+        while widths.iter().sum::<usize>() > available {
+            let Some(widest) = widths.iter_mut().max() else {
+                break;
+            };
+            if *widest <= 3 {
+                break;
+            }
+            *widest -= 1;
+        }
+    }
+
+    let line = |cells: &[String]| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| pad(&truncate(cell, *width), *width))
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+
+    let mut lines = vec![terminal.paint(BOLD, &line(columns))];
+    if terminal.styled {
+        let rule: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+        lines.push(terminal.paint(DIM, &rule.join("  ")));
+    }
+    lines.extend(rows.iter().map(|row| line(row)));
+    Ok(lines.join("\n"))
+}
+
+/// Renders a `kv` instruction: an object, or an array of `[key, value]`
+/// pairs to keep their order.
+pub fn kv(content: &str, terminal: &Terminal) -> Result<String, String> {
+    let invalid = || "Invalid kv instruction: expected an object or [key, value] pairs".to_string();
+    let pairs: Vec<(String, String)> = match serde_json::from_str(content).map_err(|_| invalid())? {
+        Value::Object(object) => object
+            .iter()
+            .map(|(key, value)| (key.clone(), text_of(value)))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item.as_array().map(Vec::as_slice) {
+                Some([key, value]) => Ok((text_of(key), text_of(value))),
+                _ => Err(invalid()),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err(invalid()),
+    };
+
+    let width = pairs
+        .iter()
+        .map(|(key, _)| key.chars().count() + 1)
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<String> = pairs
+        .iter()
+        .map(|(key, value)| {
+            let label = pad(&format!("{}:", key), width);
+            format!("{}  {}", terminal.paint(BOLD, &label), value)
+                .trim_end()
+                .to_string()
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+static INLINE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`]+)`").unwrap());
+static LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([^\]]+)\]\(([^)\s]+)\)").unwrap());
+static STRONG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\*\*([^*]+)\*\*|__([^_]+)__").unwrap());
+static EMPHASIS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\*([^*\s][^*]*)\*|\b_([^_\s][^_]*)_\b").unwrap());
+static HEADING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(#{1,6})\s+(.*)$").unwrap());
+static BULLET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\s*)[-*+]\s+(.*)$").unwrap());
+static RULE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*([-*_])(\s*[-*_]){2,}\s*$").unwrap());
+
+fn render_inline(text: &str, terminal: &Terminal) -> String {
+    let first = |captures: &Captures| {
+        captures
+            .get(1)
+            .or_else(|| captures.get(2))
+            .map(|found| found.as_str().to_string())
+            .unwrap_or_default()
+    };
+    let text = LINK.replace_all(text, |captures: &Captures| {
+        format!(
+            "{} ({})",
+            &captures[1],
+            terminal.paint(UNDERLINE, &captures[2])
+        )
+    });
+    let text = STRONG.replace_all(&text, |captures: &Captures| {
+        terminal.paint(BOLD, &first(captures))
+    });
+    let text = EMPHASIS.replace_all(&text, |captures: &Captures| {
+        terminal.paint(ITALIC, &first(captures))
+    });
+    INLINE_CODE
+        .replace_all(&text, |captures: &Captures| {
+            terminal.paint(CYAN, &captures[1])
+        })
+        .to_string()
+}
+
+/// Renders Markdown for a terminal: headings, emphasis, code, links, lists,
+/// quotes and rules. Without styling, the Markdown is printed as is.
+pub fn markdown(content: &str, terminal: &Terminal) -> String {
+    if !terminal.styled {
+        return content.trim_end().to_string();
+    }
+
+    let rule_width = terminal.width.unwrap_or(80).min(40);
+
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in content.trim_end().lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(format!("    {}", terminal.paint(DIM, line)));
+        } else if let Some(captures) = HEADING.captures(line) {
+            let style = match captures[1].len() {
+                1 | 2 => format!("{}{}", BOLD, UNDERLINE),
+                _ => BOLD.to_string(),
+            };
+            lines.push(terminal.paint(&style, &render_inline(&captures[2], terminal)));
+        } else if RULE.is_match(line) {
+            lines.push(terminal.paint(DIM, &"─".repeat(rule_width)));
+        } else if let Some(captures) = BULLET.captures(line) {
+            lines.push(format!(
+                "{}• {}",
+                &captures[1],
+                render_inline(&captures[2], terminal)
+            ));
+        } else if let Some(quote) = line.strip_prefix('>') {
+            let quote = render_inline(quote.trim_start(), terminal);
+            lines.push(format!("{} {}", terminal.paint(DIM, "│"), quote));
+        } else {
+            lines.push(render_inline(line, terminal));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Content of a `progress` instruction: `current` out of `total`, or a
+/// `percent`.
+#[derive(Debug, Deserialize)]
+struct ProgressContent {
+    #[serde(default)]
+    label: String,
+    #[serde(default)]
+    current: Option<f64>,
+    #[serde(default)]
+    total: Option<f64>,
+    #[serde(default)]
+    percent: Option<f64>,
+}
+
+/// Renders a `progress` instruction, returning its label with the line.
+pub fn progress(content: &str, terminal: &Terminal) -> Result<(String, String), String> {
+    let content: ProgressContent = serde_json::from_str(content)
+        .map_err(|e| format!("Invalid progress instruction: {}", e))?;
+    let (percent, count) = match (content.percent, content.current, content.total) {
+        (Some(percent), _, _) => (percent, None),
+        (None, Some(current), Some(total)) if total > 0.0 => (
+            current / total * 100.0,
+            Some(format!(" ({}/{})", current, total)),
+        ),
+        _ => return Err("A progress instruction needs percent, or current and total".to_string()),
+    };
+    let percent = percent.clamp(0.0, 100.0);
+    let count = count.unwrap_or_default();
+
+    let label = match content.label.is_empty() {
+        true => String::new(),
+        false => format!("{} ", content.label),
+    };
+    let line = match terminal.styled {
+        true => {
+            let bar_width = terminal
+                .width
+                .unwrap_or(80)
+                .saturating_sub(30)
+                .clamp(10, 30);
+            let filled = (percent / 100.0 * bar_width as f64).round() as usize;
+            format!(
+                "{}{}{} {:.0}%{}",
+                label,
+                terminal.paint(CYAN, &"█".repeat(filled)),
+                terminal.paint(DIM, &"░".repeat(bar_width - filled)),
+                percent,
+                count
+            )
+        }
+        false => format!("{}{:.0}%{}", label, percent, count),
+    };
+    Ok((content.label, line))
+}
+
+/// Severity of a message, from the `level` of a `print` instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Info,
+    Success,
+    Warn,
+    Error,
+    Debug,
+}
+
+impl Level {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "info" => Ok(Level::Info),
+            "success" => Ok(Level::Success),
+            "warn" => Ok(Level::Warn),
+            "error" => Ok(Level::Error),
+            "debug" => Ok(Level::Debug),
+            _ => Err(format!(
+                "Unknown level: {} (expected info, success, warn, error or debug)",
+                name
+            )),
+        }
+    }
+
+    /// Warnings, errors and debug messages go to stderr, keeping stdout for
+    /// results.
+    pub fn to_stderr(self) -> bool {
+        matches!(self, Level::Warn | Level::Error | Level::Debug)
+    }
+
+    /// The message with the symbol of its level, or a prefix without styling.
+    pub fn format(self, text: &str, terminal: &Terminal) -> String {
+        if !terminal.styled {
+            return match self {
+                Level::Info | Level::Success => text.to_string(),
+                Level::Warn => format!("Warning: {}", text),
+                Level::Error => format!("Error: {}", text),
+                Level::Debug => format!("Debug: {}", text),
+            };
+        }
+        match self {
+            Level::Info => format!("{} {}", terminal.paint(BLUE, "ℹ"), text),
+            Level::Success => format!("{} {}", terminal.paint(GREEN, "✓"), text),
+            Level::Warn => format!("{} {}", terminal.paint(YELLOW, "⚠"), text),
+            Level::Error => format!("{} {}", terminal.paint(RED, "✗"), text),
+            Level::Debug => terminal.paint(DIM, &format!("· {}", text)),
+        }
+    }
+}
+
+/// A spinner shown on stderr while an instruction runs.
+pub struct Spinner {
+    message: String,
+    terminal: Terminal,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// Starts animating, or only prints the message without styling.
+    pub fn start(message: String) -> Self {
+        let terminal = Terminal::stderr();
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = match terminal.styled {
+            true => {
+                let running = running.clone();
+                let message = message.clone();
+                Some(thread::spawn(move || {
+                    let mut frame = 0;
+                    while running.load(Ordering::Relaxed) {
+                        eprint!("\r{} {}", SPINNER_FRAMES[frame], message);
+                        let _ = io::stderr().flush();
+                        frame = (frame + 1) % SPINNER_FRAMES.len();
+                        thread::sleep(Duration::from_millis(80));
+                    }
+                }))
+            }
+            false => {
+                eprintln!("{}", message);
+                None
+            }
+        };
+        Spinner {
+            message,
+            terminal,
+            running,
+            thread,
+        }
+    }
+
+    /// Replaces the spinner with a mark saying how it went.
+    pub fn stop(mut self, succeeded: bool) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+            let mark = match succeeded {
+                true => self.terminal.paint(GREEN, "✓"),
+                false => self.terminal.paint(RED, "✗"),
+            };
+            eprintln!("\r\x1b[2K{} {}", mark, self.message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: Terminal = Terminal {
+        styled: false,
+        width: None,
+    };
+
+    #[test]
+    fn test_table() {
+        let content = r#"{"columns": ["Name", "Status"], "rows": [
+            ["web", "running"], {"Name": "worker", "Status": null}]}"#;
+        assert_eq!(
+            table(content, &PLAIN).unwrap(),
+            "Name    Status\nweb     running\nworker"
+        );
+
+        let narrow = Terminal {
+            styled: false,
+            width: Some(12),
+        };
+        assert_eq!(
+            table(content, &narrow).unwrap(),
+            "Name   Stat…\nweb    runn…\nwork…"
+        );
+        assert!(table(r#"{"rows": []}"#, &PLAIN).is_err());
+    }
+
+    #[test]
+    fn test_kv() {
+        let content = r#"[["Name", "web"], ["Replicas", 3]]"#;
+        assert_eq!(kv(content, &PLAIN).unwrap(), "Name:      web\nReplicas:  3");
+        assert!(kv(r#"[["Name"]]"#, &PLAIN).is_err());
+    }
+
+    #[test]
+    fn test_markdown() {
+        let content = "# Deploy\n\n- Run `make` with **care**\n> see [docs](https://x.dev)";
+        assert_eq!(markdown(content, &PLAIN), content);
+
+        let styled = Terminal {
+            styled: true,
+            width: Some(80),
+        };
+        let rendered = markdown(content, &styled);
+        assert!(rendered.starts_with("\x1b[1m\x1b[4mDeploy\x1b[0m"));
+        assert!(rendered.contains("• Run \x1b[36mmake\x1b[0m with \x1b[1mcare\x1b[0m"));
+        assert!(rendered.contains("docs (\x1b[4mhttps://x.dev\x1b[0m)"));
+    }
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(Level::Success.format("Deployed", &PLAIN), "Deployed");
+        assert_eq!(Level::Warn.format("Disk low", &PLAIN), "Warning: Disk low");
+        let styled = Terminal {
+            styled: true,
+            width: Some(80),
+        };
+        assert_eq!(
+            Level::Error.format("Failed", &styled),
+            "\x1b[31m✗\x1b[0m Failed"
+        );
+        assert!(Level::Warn.to_stderr() && !Level::Info.to_stderr());
+        assert!(Level::parse("fatal").is_err());
+    }
+
+    #[test]
+    fn test_progress() {
+        let (label, line) = progress(
+            r#"{"label": "Uploading", "current": 3, "total": 4}"#,
+            &PLAIN,
+        )
+        .unwrap();
+        assert_eq!(label, "Uploading");
+        assert_eq!(line, "Uploading 75% (3/4)");
+        assert_eq!(progress(r#"{"percent": 40}"#, &PLAIN).unwrap().1, "40%");
+        assert!(progress(r#"{"label": "x"}"#, &PLAIN).is_err());
+    }
+}
@@ -0,0 +1,250 @@
+use super::InstructionContext;
+use crate::config::data::{ExecuteMode, ExecutePolicy};
+use crate::utils::prompt::ask;
+use regex::Regex;
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Characters that let a command run more than what its prefix says.
+const SHELL_OPERATORS: &[char] = &[';', '&', '|', '`', '$', '(', ')', '<', '>', '\n'];
+
+/// What to do with a command sent by the server.
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    Run,
+    /// `--self:dry-run`: show it instead.
+    DryRun,
+    Refused(String),
+}
+
+/// Compiles the `allow_patterns` of a policy to match whole commands, when
+/// the config is loaded.
+pub fn compile_patterns(policy: Option<&ExecutePolicy>) -> Result<Vec<Regex>, String> {
+    policy
+        .map(|policy| policy.allow_patterns.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|pattern| {
+            Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| format!("Invalid allow pattern {}: {}", pattern, e))
+        })
+        .collect()
+}
+
+/// Whether the policy's allowlist accepts the command. Prefixes only match
+/// whole words and commands without shell operators; `patterns` must match
+/// the whole command.
+fn allowlisted(command: &str, policy: &ExecutePolicy, patterns: &[Regex]) -> bool {
+    let command = command.trim();
+    if !command.contains(SHELL_OPERATORS) {
+        let prefixed = policy.allow.iter().any(|prefix| {
+            let prefix = prefix.trim();
+            command == prefix
+                || command
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with(char::is_whitespace))
+        });
+        if prefixed {
+            return true;
+        }
+    }
+    patterns.iter().any(|pattern| pattern.is_match(command))
+}
+
+/// Applies the configured policy to a command, asking for confirmation
+/// when it says so.
+pub fn check(command: &str, context: &InstructionContext) -> Decision {
+    let policy = context
+        .config
+        .as_ref()
+        .and_then(|config| config.execute.clone())
+        .unwrap_or_default();
+
+    let refusal = match policy.mode {
+        ExecuteMode::Allow | ExecuteMode::Confirm => None,
+        ExecuteMode::Deny => Some("commands are denied by the execute policy".to_string()),
+        ExecuteMode::Allowlist => match allowlisted(command, &policy, &context.allow_patterns) {
+            true => None,
+            false => Some("the command is not in the allowlist".to_string()),
+        },
+    };
+    if let Some(reason) = refusal {
+        return Decision::Refused(reason);
+    }
+    if context.dry_run {
+        return Decision::DryRun;
+    }
+    if policy.mode != ExecuteMode::Confirm {
+        return Decision::Run;
+    }
+
+    if !context.interactive {
+        return Decision::Refused("no terminal to confirm it on".to_string());
+    }
+    eprintln!(
+        "The server wants to run:\n  {}",
+        command.replace('\n', "\n  ")
+    );
+    match ask("Run it? [y/N]", None).map(|answer| answer.trim().to_lowercase()) {
+        Ok(answer) if answer == "y" || answer == "yes" => Decision::Run,
+        _ => Decision::Refused("declined".to_string()),
+    }
+}
+
+/// `check` for actions reported by the caller: a dry run prints the action
+/// and returns `false`, a refusal is audited and returned as its reason.
+pub fn permit(command: &str, context: &InstructionContext) -> Result<bool, String> {
+    match check(command, context) {
+        Decision::Run => Ok(true),
+        Decision::DryRun => {
+            println!("Would run: {}", command);
+            Ok(false)
+        }
+        Decision::Refused(reason) => {
+            audit(context, command, "refused", None);
+            Err(reason)
+        }
+    }
+}
+
+fn audit_path(context: &InstructionContext) -> Option<PathBuf> {
+    let configured = context
+        .config
+        .as_ref()
+        .and_then(|config| config.execute.as_ref())
+        .and_then(|policy| policy.audit_log.as_ref());
+    match configured {
+        Some(path) => Some(PathBuf::from(path)),
+        None if context.config_path.as_os_str().is_empty() => None,
+        None => Some(context.config_path.with_file_name("audit.log")),
+    }
+}
+
+/// Appends a JSON line about a command to the audit log. `result` is `ran`
+/// or `refused`.
+pub fn audit(context: &InstructionContext, command: &str, result: &str, exit_code: Option<i32>) {
+    let Some(path) = audit_path(context) else {
+        return;
+    };
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let entry = json!({
+        "time": time,
+        "endpoint": context.endpoint,
+        "command": command,
+        "result": result,
+        "exit_code": exit_code,
+    });
+
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let mut options = OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", entry));
+    if let Err(e) = written {
+        eprintln!("Failed to write the audit log {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::data::Config;
+    use serde_json::Value;
+    use tempfile::TempDir;
+
+    fn context(policy: ExecutePolicy) -> InstructionContext {
+        InstructionContext {
+            allow_patterns: compile_patterns(Some(&policy)).unwrap(),
+            config: Some(Config {
+                execute: Some(policy),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let policy = ExecutePolicy {
+            mode: ExecuteMode::Allowlist,
+            allow: vec!["git pull".to_string()],
+            allow_patterns: vec![r"npm run (build|test)".to_string()],
+            ..Default::default()
+        };
+        let context = context(policy);
+
+        assert_eq!(check("git pull", &context), Decision::Run);
+        assert_eq!(check("git pull origin main", &context), Decision::Run);
+        assert_eq!(check("npm run test", &context), Decision::Run);
+        for command in ["git pullx", "git pull; rm -rf /", "npm run test && rm x"] {
+            assert!(matches!(check(command, &context), Decision::Refused(_)));
+        }
+
+        let invalid = ExecutePolicy {
+            allow_patterns: vec!["npm run (build".to_string()],
+            ..Default::default()
+        };
+        assert!(compile_patterns(Some(&invalid))
+            .unwrap_err()
+            .starts_with("Invalid allow pattern npm run (build:"));
+        assert!(compile_patterns(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut context = context(ExecutePolicy {
+            mode: ExecuteMode::Deny,
+            ..Default::default()
+        });
+        assert!(matches!(check("ls", &context), Decision::Refused(_)));
+
+        context.config.as_mut().unwrap().execute = Some(ExecutePolicy {
+            mode: ExecuteMode::Confirm,
+            ..Default::default()
+        });
+        assert_eq!(
+            check("ls", &context),
+            Decision::Refused("no terminal to confirm it on".to_string())
+        );
+        context.dry_run = true;
+        assert_eq!(check("ls", &context), Decision::DryRun);
+
+        assert_eq!(check("ls", &InstructionContext::default()), Decision::Run);
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let context = InstructionContext {
+            config_path: temp_dir.path().join("config.json"),
+            endpoint: "http://localhost/deploy".to_string(),
+            ..Default::default()
+        };
+        audit(&context, "git pull", "ran", Some(0));
+        audit(&context, "rm -rf /", "refused", None);
+
+        let log = fs::read_to_string(temp_dir.path().join("audit.log")).unwrap();
+        let entries: Vec<Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["command"], "git pull");
+        assert_eq!(entries[0]["endpoint"], "http://localhost/deploy");
+        assert_eq!(entries[1]["result"], "refused");
+    }
+}
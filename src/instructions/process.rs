@@ -0,0 +1,271 @@
+use super::template::shell_quote;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Exit code reported for commands that ran out of time, as `timeout(1)` does.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// A command for an `execute` instruction.
+#[derive(Debug, Default)]
+pub struct Script {
+    program: String,
+    args: Vec<String>,
+    /// How the command is shown and checked against the execute policy.
+    pub display: String,
+    pub cwd: Option<PathBuf>,
+    pub env: HashMap<String, String>,
+    pub stdin: Option<String>,
+    pub timeout: Option<Duration>,
+    /// Keep stdout instead of printing it.
+    pub capture: bool,
+}
+
+impl Script {
+    /// A script run with `sh -c`.
+    pub fn shell(script: &str) -> Self {
+        Script {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            display: script.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// A program and its arguments, run without a shell.
+    pub fn argv(command: &[String]) -> Result<Self, String> {
+        let (program, args) = command
+            .split_first()
+            .ok_or("The command of an execute instruction is empty")?;
+        let display = command
+            .iter()
+            .map(|word| shell_word(word))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(Script {
+            program: program.clone(),
+            args: args.to_vec(),
+            display,
+            ..Default::default()
+        })
+    }
+
+    /// The command with its working directory, environment and input, as
+    /// the shell would take it. This is what the execute policy checks, the
+    /// user confirms and the audit log records, so that none of them can
+    /// change what an allowed command does unseen.
+    pub fn describe(&self) -> String {
+        let mut described = String::new();
+        if let Some(cwd) = &self.cwd {
+            described.push_str(&format!("cd {} && ", shell_word(&cwd.to_string_lossy())));
+        }
+        let mut env: Vec<_> = self.env.iter().collect();
+        env.sort();
+        for (key, value) in env {
+            described.push_str(&format!("{}={} ", key, shell_word(value)));
+        }
+        described.push_str(&self.display);
+        if let Some(stdin) = &self.stdin {
+            described.push_str(&format!(" <<< {}", shell_quote(stdin)));
+        }
+        described
+    }
+}
+
+/// A word as typed in the shell, quoted unless it is plain.
+fn shell_word(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+    match plain {
+        true => word.to_string(),
+        false => shell_quote(word),
+    }
+}
+
+/// Exit code of a script, with its stdout when captured.
+#[derive(Debug, Default)]
+pub struct ScriptResult {
+    /// `None` when the script couldn't run or was killed by a signal.
+    pub code: Option<i32>,
+    pub stdout: Option<String>,
+    pub timed_out: bool,
+}
+
+/// Kills the command and everything it started.
+#[cfg(unix)]
+fn kill_group(child: &mut Child) {
+    // The child leads its own process group, see `run`
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_group(child: &mut Child) {
+    let _ = child.kill();
+}
+
+fn wait(child: &mut Child, timeout: Option<Duration>) -> io::Result<(ExitStatus, bool)> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(|status| (status, false));
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, false));
+        }
+        if Instant::now() >= deadline {
+            kill_group(child);
+            return child.wait().map(|status| (status, true));
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Runs the script, feeding it `stdin` and killing it with its children
+/// after `timeout`.
+pub fn run(script: &Script) -> ScriptResult {
+    let mut command = Command::new(&script.program);
+    command.args(&script.args).envs(&script.env);
+    if let Some(cwd) = &script.cwd {
+        command.current_dir(cwd);
+    }
+    if script.stdin.is_some() {
+        command.stdin(Stdio::piped());
+    }
+    if script.capture {
+        command.stdout(Stdio::piped());
+    }
+    #[cfg(unix)]
+    if script.timeout.is_some() {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return ScriptResult::default();
+        }
+    };
+
+    if let (Some(mut pipe), Some(input)) = (child.stdin.take(), script.stdin.clone()) {
+        thread::spawn(move || {
+            let _ = pipe.write_all(input.as_bytes());
+        });
+    }
+    // Read while waiting, so that a full pipe doesn't block the command
+    let reader = child.stdout.take().map(|mut pipe| {
+        thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = pipe.read_to_end(&mut output);
+            output
+        })
+    });
+
+    let waited = wait(&mut child, script.timeout);
+    let stdout = reader.map(|reader| {
+        let output = reader.join().unwrap_or_default();
+        String::from_utf8_lossy(&output)
+            .trim_end_matches(['\r', '\n'])
+            .to_string()
+    });
+
+    match waited {
+        Ok((_, true)) => ScriptResult {
+            code: Some(TIMEOUT_EXIT_CODE),
+            stdout,
+            timed_out: true,
+        },
+        Ok((status, false)) => ScriptResult {
+            code: status.code(),
+            stdout,
+            timed_out: false,
+        },
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ScriptResult::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(words: &[&str]) -> Script {
+        let words: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+        Script::argv(&words).unwrap()
+    }
+
+    #[test]
+    fn test_argv_without_shell() {
+        let mut script = argv(&["printf", "%s", "it's $HOME; ok"]);
+        script.capture = true;
+        assert_eq!(script.display, r"printf %s 'it'\''s $HOME; ok'");
+
+        let result = run(&script);
+        assert_eq!(result.code, Some(0));
+        assert_eq!(result.stdout.as_deref(), Some("it's $HOME; ok"));
+        assert!(Script::argv(&[]).is_err());
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut script = argv(&["git", "pull"]);
+        assert_eq!(script.describe(), "git pull");
+
+        script.cwd = Some(PathBuf::from("/srv/my app"));
+        script
+            .env
+            .insert("GIT_SSH_COMMAND".to_string(), "sh -c id".to_string());
+        script.env.insert("A".to_string(), "1".to_string());
+        script.stdin = Some("yes".to_string());
+        assert_eq!(
+            script.describe(),
+            "cd '/srv/my app' && A=1 GIT_SSH_COMMAND='sh -c id' git pull <<< 'yes'"
+        );
+    }
+
+    #[test]
+    fn test_cwd_env_and_stdin() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut script = Script::shell("pwd && echo $GREETING && cat");
+        script.cwd = Some(temp_dir.path().canonicalize().unwrap());
+        script
+            .env
+            .insert("GREETING".to_string(), "hello".to_string());
+        script.stdin = Some("from stdin".to_string());
+        script.capture = true;
+
+        let result = run(&script);
+        assert_eq!(
+            result.stdout.unwrap(),
+            format!(
+                "{}\nhello\nfrom stdin",
+                temp_dir.path().canonicalize().unwrap().display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_timeout_kills_process_group() {
+        let mut script = Script::shell("sleep 5 & sleep 5; echo done");
+        script.timeout = Some(Duration::from_millis(200));
+        script.capture = true;
+
+        let started = Instant::now();
+        let result = run(&script);
+        assert!(result.timed_out);
+        assert_eq!(result.code, Some(TIMEOUT_EXIT_CODE));
+        // The background sleep held stdout open until it was killed too
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(result.stdout.as_deref(), Some(""));
+    }
+}
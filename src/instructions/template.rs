@@ -0,0 +1,561 @@
+use super::InstructionContext;
+use crate::utils::git::{get_current_branch, get_git_repo_info, get_git_root};
+use serde_json::Value;
+use std::env;
+
+/// Wraps a value in single quotes for `sh`, so that it stays one argument.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Where a script is at, as far as quoting goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ShellState {
+    Plain,
+    /// `'...'`, or `$'...'` with `ansi_c`.
+    Single {
+        ansi_c: bool,
+    },
+    Double,
+    Comment,
+}
+
+/// A here-document being opened or read.
+#[derive(Debug, Clone, PartialEq)]
+enum Heredoc {
+    /// Right after `<<`, before its first character.
+    Opened,
+    /// Reading the delimiter word, quotes removed. `<<-` strips tabs.
+    Delimiter {
+        word: String,
+        quoted: bool,
+        strip_tabs: bool,
+    },
+}
+
+/// Follows the quoting of a script's text, so that placeholders are only
+/// filled where a quoted value stays a single word.
+#[derive(Debug)]
+struct ShellScan {
+    state: ShellState,
+    /// The previous character was an unquoted `\`.
+    escaped: bool,
+    heredoc: Option<Heredoc>,
+    /// Delimiters of the here-documents whose bodies start on the next line,
+    /// and whether their lines have tabs stripped.
+    pending: Vec<(String, bool)>,
+    /// The current line of a here-document body, which expands `$(...)`
+    /// whatever the quotes.
+    body: Option<String>,
+    previous: Option<char>,
+}
+
+impl ShellScan {
+    fn new() -> Self {
+        ShellScan {
+            state: ShellState::Plain,
+            escaped: false,
+            heredoc: None,
+            pending: Vec::new(),
+            body: None,
+            previous: None,
+        }
+    }
+
+    fn feed(&mut self, text: &str) {
+        for c in text.chars() {
+            if let Some(line) = &mut self.body {
+                if c != '\n' {
+                    line.push(c);
+                    continue;
+                }
+                let (delimiter, strip_tabs) = &self.pending[0];
+                let line = match strip_tabs {
+                    true => line.trim_start_matches('\t'),
+                    false => line.as_str(),
+                };
+                if line == delimiter {
+                    self.pending.remove(0);
+                }
+                self.body = match self.pending.is_empty() {
+                    true => None,
+                    false => Some(String::new()),
+                };
+                self.previous = Some(c);
+                continue;
+            }
+
+            let (state, escaped) = (self.state, self.escaped);
+            if self.escaped {
+                self.escaped = false;
+            } else {
+                match (self.state, c) {
+                    (ShellState::Plain | ShellState::Double, '\\') => self.escaped = true,
+                    (ShellState::Single { ansi_c: true }, '\\') => self.escaped = true,
+                    (ShellState::Plain, '\'') => {
+                        let ansi_c = self.previous == Some('$');
+                        self.state = ShellState::Single { ansi_c };
+                    }
+                    (ShellState::Plain, '"') => self.state = ShellState::Double,
+                    (ShellState::Plain, '#')
+                        if self
+                            .previous
+                            .is_none_or(|p| p.is_whitespace() || ";&|()<>".contains(p)) =>
+                    {
+                        self.state = ShellState::Comment
+                    }
+                    (ShellState::Plain, '<')
+                        if self.previous == Some('<') && self.heredoc.is_none() =>
+                    {
+                        self.heredoc = Some(Heredoc::Opened);
+                        self.previous = Some(c);
+                        continue;
+                    }
+                    (ShellState::Single { .. }, '\'') | (ShellState::Double, '"') => {
+                        self.state = ShellState::Plain
+                    }
+                    (ShellState::Comment, '\n') => self.state = ShellState::Plain,
+                    _ => {}
+                }
+            }
+            self.read_delimiter(c, state, escaped);
+            // Bodies start after the line that opened them
+            if c == '\n' && !escaped && self.state == ShellState::Plain && !self.pending.is_empty()
+            {
+                self.body = Some(String::new());
+            }
+            self.previous = Some(c);
+        }
+    }
+
+    /// Collects the delimiter word after `<<`, given the state before `c`.
+    fn read_delimiter(&mut self, c: char, state: ShellState, escaped: bool) {
+        let Some(heredoc) = self.heredoc.take() else {
+            return;
+        };
+        let (mut word, mut quoted, strip_tabs) = match heredoc {
+            // `<<<` is a here-string, which takes a plain word
+            Heredoc::Opened if c == '<' => return,
+            Heredoc::Opened if c == '-' => {
+                self.heredoc = Some(Heredoc::Delimiter {
+                    word: String::new(),
+                    quoted: false,
+                    strip_tabs: true,
+                });
+                return;
+            }
+            Heredoc::Opened => (String::new(), false, false),
+            Heredoc::Delimiter {
+                word,
+                quoted,
+                strip_tabs,
+            } => (word, quoted, strip_tabs),
+        };
+
+        let unquoted = state == ShellState::Plain && !escaped;
+        if unquoted && (c.is_whitespace() || ";&|()<>".contains(c)) {
+            if !word.is_empty() || quoted {
+                self.pending.push((word, strip_tabs));
+                return;
+            }
+            if c == ' ' || c == '\t' {
+                self.heredoc = Some(Heredoc::Delimiter {
+                    word,
+                    quoted,
+                    strip_tabs,
+                });
+            }
+            return;
+        }
+        if self.state != state || self.escaped {
+            // A quote or backslash, removed from the delimiter
+            quoted = true;
+        } else {
+            word.push(c);
+        }
+        self.heredoc = Some(Heredoc::Delimiter {
+            word,
+            quoted,
+            strip_tabs,
+        });
+    }
+
+    /// Why a quoted value can't go at the current position, if it can't.
+    fn unsafe_position(&self) -> Option<&'static str> {
+        match self.state {
+            _ if self.body.is_some() => Some("inside a here-document"),
+            _ if self.heredoc.is_some() => Some("after <<"),
+            _ if self.escaped => Some("after a backslash"),
+            ShellState::Single { .. } | ShellState::Double => Some("inside quotes"),
+            ShellState::Comment => Some("inside a comment"),
+            ShellState::Plain => None,
+        }
+    }
+}
+
+/// Replaces `{{name}}` placeholders using `lookup`, quoting values for the
+/// shell when `shell` is set. Unknown names are an error, and so are
+/// placeholders of a script where the quoted value wouldn't stay one word,
+/// e.g. `'{{name}}'`: values are quoted already.
+pub fn render(
+    text: &str,
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+    shell: bool,
+) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = text;
+    let mut scan = ShellScan::new();
+
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + length].trim();
+        if shell {
+            scan.feed(&rest[..start]);
+            if let Some(position) = scan.unsafe_position() {
+                return Err(format!(
+                    "Placeholder {{{{{}}}}} can't be used {} in a script: values are quoted for the shell already",
+                    name, position
+                ));
+            }
+            // What follows comes right after a quoted word
+            scan.previous = Some('\'');
+        }
+        let value = lookup(name).ok_or_else(|| format!("Unknown variable: {}", name))?;
+
+        output.push_str(&rest[..start]);
+        if shell {
+            output.push_str(&shell_quote(&value));
+        } else {
+            output.push_str(&value);
+        }
+        rest = &rest[start + 2 + length + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn render_value(
+    value: Value,
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+) -> Result<Value, String> {
+    Ok(match value {
+        Value::String(text) => Value::String(render(&text, lookup, false)?),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| render_value(item, lookup))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, item)| Ok((key, render_value(item, lookup)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        other => other,
+    })
+}
+
+/// Git and working directory values. The remote is looked up on first use.
+#[derive(Debug, Default, Clone)]
+pub struct LocalValues {
+    git: Option<Option<(String, String, String)>>,
+}
+
+impl LocalValues {
+    fn git(&mut self) -> Option<&(String, String, String)> {
+        self.git.get_or_insert_with(get_git_repo_info).as_ref()
+    }
+
+    fn get(&mut self, name: &str, env_allowlist: &[String]) -> Option<String> {
+        match name {
+            "cwd" => env::current_dir()
+                .ok()
+                .map(|dir| dir.to_string_lossy().to_string()),
+            "git.root" => get_git_root(),
+            "git.remote" => self.git().map(|git| git.0.clone()),
+            "git.repo" => self.git().map(|git| git.1.clone()),
+            "git.branch" => get_current_branch(),
+            _ => {
+                let var = name.strip_prefix("env.")?;
+                if env_allowlist.iter().any(|allowed| allowed == var) {
+                    env::var(var).ok()
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl InstructionContext {
+    /// A template variable: a captured value, an answer, the exit code of the
+    /// last script as `status`, or a local value.
+    pub fn variable(&mut self, name: &str) -> Option<String> {
+        if let Some(value) = self.variables.get(name) {
+            return Some(value.clone());
+        }
+        if let Some(value) = self.answers.get(name) {
+            return Some(match value {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            });
+        }
+        if name == "status" {
+            return self.status.map(|code| code.to_string());
+        }
+        let allowlist = self
+            .config
+            .as_ref()
+            .map(|config| config.template_env.clone())
+            .unwrap_or_default();
+        self.local.get(name, &allowlist)
+    }
+}
+
+/// Fills the placeholders of a text field other than the content.
+pub fn render_text(text: &str, context: &mut InstructionContext) -> Result<String, String> {
+    render(text, &mut |name: &str| context.variable(name), false)
+}
+
+/// Fills the placeholders of an instruction's content. Structured content
+/// has its strings filled one by one, so values can't break the JSON; a
+/// script is always text, whatever it looks like.
+pub fn render_content(
+    content: Option<&str>,
+    context: &mut InstructionContext,
+    shell: bool,
+) -> Result<Option<String>, String> {
+    let Some(content) = content else {
+        return Ok(None);
+    };
+    if !content.contains("{{") {
+        return Ok(Some(content.to_string()));
+    }
+
+    let mut lookup = |name: &str| context.variable(name);
+    match serde_json::from_str::<Value>(content) {
+        Ok(value @ (Value::Object(_) | Value::Array(_))) if !shell => {
+            Ok(Some(render_value(value, &mut lookup)?.to_string()))
+        }
+        _ => render(content, &mut lookup, shell).map(Some),
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|value| value.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+/// Evaluates the `if` of an instruction: `name == value`, `name != value`,
+/// `name` or `!name`. The left side is a variable, empty when unknown, and
+/// may be written `{{name}}`; the right side is a value, quotes optional. A
+/// variable alone is true unless it is empty, `0` or `false`.
+pub fn evaluate_condition(
+    condition: &str,
+    context: &mut InstructionContext,
+) -> Result<bool, String> {
+    let mut value_of = |name: &str| {
+        let name = name.trim();
+        let name = name
+            .strip_prefix("{{")
+            .and_then(|name| name.strip_suffix("}}"))
+            .unwrap_or(name)
+            .trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Invalid condition: {}", condition));
+        }
+        Ok(context.variable(name).unwrap_or_default())
+    };
+
+    for (operator, equal) in [("==", true), ("!=", false)] {
+        if let Some((name, expected)) = condition.split_once(operator) {
+            let value = value_of(name)?;
+            return Ok((value == unquote(expected.trim())) == equal);
+        }
+    }
+
+    let (name, negated) = match condition.trim().strip_prefix('!') {
+        Some(name) => (name, true),
+        None => (condition, false),
+    };
+    let value = value_of(name)?;
+    let truthy = !matches!(value.as_str(), "" | "0" | "false");
+    Ok(truthy != negated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::data::Config;
+    use serde_json::json;
+
+    fn context() -> InstructionContext {
+        let mut context = InstructionContext {
+            config: Some(Config::default()),
+            ..Default::default()
+        };
+        context
+            .variables
+            .insert("version".to_string(), "1.2.0".to_string());
+        context.answers.insert("replicas".to_string(), json!(3));
+        context
+    }
+
+    #[test]
+    fn test_render_content() {
+        let mut context = context();
+        let rendered = render_content(
+            Some("Deploying {{ version }} with {{replicas}} replicas"),
+            &mut context,
+            false,
+        );
+        assert_eq!(
+            rendered.unwrap().unwrap(),
+            "Deploying 1.2.0 with 3 replicas"
+        );
+
+        assert_eq!(
+            render_content(Some("{{missing}}"), &mut context, false).unwrap_err(),
+            "Unknown variable: missing"
+        );
+        assert_eq!(
+            render_content(Some("no placeholders"), &mut context, false).unwrap(),
+            Some("no placeholders".to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_content_structured() {
+        let mut context = context();
+        context
+            .variables
+            .insert("quote".to_string(), r#"say "hi""#.to_string());
+        let rendered = render_content(
+            Some(r#"{"path": "/releases/{{version}}", "body": {"note": "{{quote}}"}}"#),
+            &mut context,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&rendered).unwrap(),
+            json!({"path": "/releases/1.2.0", "body": {"note": "say \"hi\""}})
+        );
+    }
+
+    #[test]
+    fn test_render_shell_quotes_values() {
+        let mut context = context();
+        context
+            .variables
+            .insert("name".to_string(), "it's; rm -rf /".to_string());
+        assert_eq!(
+            render_content(Some("echo {{name}}"), &mut context, true)
+                .unwrap()
+                .unwrap(),
+            r"echo 'it'\''s; rm -rf /'"
+        );
+    }
+
+    #[test]
+    fn test_render_shell_refuses_quoted_placeholders() {
+        let mut context = context();
+        context
+            .variables
+            .insert("name".to_string(), "x'; rm -rf ~; '".to_string());
+
+        for script in [
+            "echo '{{name}}'",
+            "echo \"Hello {{name}}\"",
+            "echo \"$(echo {{name}})\"",
+            "echo $'{{name}}'",
+            r"echo \{{name}}",
+            "echo hi # {{name}}",
+            "cat <<EOF\n{{name}}\nEOF",
+            "cat <<-'EOF' | sh\n\tEOF2\n{{name}}\n\tEOF\n",
+            "cat <<A <<B\nA\n{{name}}\nB\n",
+            "cat <<{{name}}",
+            "[\"echo '{{name}}'\"]",
+            "[\"{{name}}\"]",
+        ] {
+            let err = render_content(Some(script), &mut context, true).unwrap_err();
+            assert!(
+                err.starts_with("Placeholder {{name}} can't be used"),
+                "{}",
+                script
+            );
+        }
+
+        // Closed or escaped quotes don't count
+        assert_eq!(
+            render_content(Some(r#"echo 'a' "b" \' {{name}}#x"#), &mut context, true)
+                .unwrap()
+                .unwrap(),
+            r#"echo 'a' "b" \' 'x'\''; rm -rf ~; '\'''#x"#
+        );
+        assert!(render_content(Some("echo '{{name}}'"), &mut context, false).is_ok());
+
+        // Here-documents end at their delimiter, and `<<<` takes a word
+        for script in [
+            "cat <<EOF\nhi\nEOF\necho {{name}}",
+            "cat <<-\"EOF\" # note\n\thi\n\tEOF\necho {{name}}",
+            "cat <<\\A <<B\nA\nB\necho {{name}}",
+            "cat <<< {{name}}",
+        ] {
+            assert!(
+                render_content(Some(script), &mut context, true).is_ok(),
+                "{}",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn test_local_values() {
+        // PATH is always set, and no test changes it
+        let mut context = context();
+        assert!(env::var("PATH").is_ok());
+        assert!(context.variable("env.PATH").is_none());
+
+        context
+            .config
+            .as_mut()
+            .unwrap()
+            .template_env
+            .push("PATH".to_string());
+        assert_eq!(context.variable("env.PATH"), env::var("PATH").ok());
+        assert_eq!(
+            context.variable("cwd"),
+            env::current_dir()
+                .ok()
+                .map(|dir| dir.to_string_lossy().to_string())
+        );
+    }
+
+    #[test]
+    fn test_evaluate_condition() {
+        let mut context = context();
+        context.status = Some(2);
+        context.answers.insert("go".to_string(), json!(false));
+
+        assert!(evaluate_condition("status != 0", &mut context).unwrap());
+        assert!(!evaluate_condition("{{ status }} == 0", &mut context).unwrap());
+        assert!(evaluate_condition("version == '1.2.0'", &mut context).unwrap());
+        assert!(evaluate_condition("version", &mut context).unwrap());
+        assert!(evaluate_condition("!go", &mut context).unwrap());
+        assert!(!evaluate_condition("missing", &mut context).unwrap());
+        assert!(evaluate_condition("missing == \"\"", &mut context).unwrap());
+        assert!(evaluate_condition("== 1", &mut context).is_err());
+    }
+}
@@ -1,24 +1,46 @@
 pub mod cli {
     pub mod args;
+    pub mod body;
+    pub mod completions;
+    pub mod help;
+    pub mod options;
     pub mod parse;
+    pub mod tokens;
 }
 
 pub mod config {
     pub mod data;
     pub mod loader;
+    pub mod resolve;
     pub mod saver;
 }
 
 pub mod utils {
     pub mod executable;
     pub mod git;
+    pub mod prompt;
+    #[cfg(test)]
+    pub mod test_server;
 }
 
 pub mod commands {
+    pub mod completions;
+    pub mod config;
+    pub mod import_openapi;
+    pub mod login;
+    pub mod manifest;
+    pub mod profile;
     pub mod set_base_url;
     pub mod set_header;
+    pub mod trust_key;
     pub mod uninstall;
+    pub mod validate;
 }
 
 pub mod instructions;
+pub mod manifest;
+pub mod oauth;
+pub mod request;
 pub mod schema;
+pub mod secrets;
+pub mod signing;
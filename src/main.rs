@@ -1,89 +1,158 @@
 pub mod cli {
     pub mod args;
+    pub mod body;
+    pub mod completions;
+    pub mod help;
+    pub mod options;
     pub mod parse;
+    pub mod tokens;
 }
 
 pub mod config {
     pub mod data;
     pub mod loader;
+    pub mod resolve;
     pub mod saver;
 }
 
 pub mod utils {
     pub mod executable;
     pub mod git;
+    pub mod prompt;
+    #[cfg(test)]
+    pub mod test_server;
 }
 
 pub mod commands {
+    pub mod completions;
+    pub mod config;
+    pub mod import_openapi;
+    pub mod login;
+    pub mod manifest;
+    pub mod profile;
     pub mod set_base_url;
     pub mod set_header;
+    pub mod trust_key;
     pub mod uninstall;
     pub mod update;
+    pub mod validate;
 }
 
 pub mod instructions;
+pub mod manifest;
+pub mod oauth;
+pub mod request;
 pub mod schema;
+pub mod secrets;
+pub mod signing;
+
+use std::io::{self, IsTerminal};
 
-use clap::Parser;
 use reqwest::blocking::Client;
-use reqwest::header::USER_AGENT;
+use reqwest::{Method, StatusCode};
 
 use crate::cli::args::Cli;
-use crate::cli::parse::parse_query_params;
-use crate::config::loader::load_config;
-use crate::instructions::process_instructions;
-use crate::schema::parse_anything_schema;
-use crate::utils::executable::get_executable_name;
-use crate::utils::git::get_git_repo_info;
+use crate::cli::body::{apply_body, build_body, read_piped_stdin, split_body_items};
+use crate::cli::completions::COMPLETE_COMMAND;
+use crate::cli::help::render_help;
+use crate::cli::options::{extract_method_verb, extract_self_options};
+use crate::cli::parse::query_params_from_tokens;
+use crate::cli::tokens::{
+    has_flag, positionals, tokenize, tokenize_with_switches, Token, INTERNAL_SWITCHES,
+};
+use crate::config::data::Config;
+use crate::config::loader::{apply_credentials, load_resolved_config};
+use crate::instructions::{compile_patterns, process_response, request_key, InstructionContext};
+use crate::manifest::{is_manifest_stale, load_local_manifest, refresh_manifest};
+use crate::oauth::refresh_after_unauthorized;
+use crate::request::{apply_default_headers, endpoint_url};
+use crate::signing::{new_nonce, NONCE_HEADER};
+use crate::utils::executable::{env_var_name, get_executable_name};
 
+use crate::commands::completions::{handle_complete, handle_completions};
+use crate::commands::config::handle_config;
+use crate::commands::import_openapi::handle_import_openapi;
+use crate::commands::login::{handle_login, handle_logout};
+use crate::commands::manifest::handle_manifest;
+use crate::commands::profile::handle_profile;
 use crate::commands::set_base_url::handle_set_base_url;
 use crate::commands::set_header::handle_set_header;
+use crate::commands::trust_key::handle_trust_key;
 use crate::commands::uninstall::handle_uninstall;
 use crate::commands::update::handle_update;
+use crate::commands::validate::handle_validate;
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = Cli::parse_preserving_separator(std::env::args());
     let version = env!("CARGO_PKG_VERSION");
-    let mut filtered_commands: Vec<String> = Vec::new();
-    let mut filtered_args: Vec<String> = Vec::new();
-    let mut args_iter = cli.commands.iter().peekable();
-
-    while let Some(arg) = args_iter.next() {
-        if arg.starts_with("--") {
-            filtered_args.push(arg.clone());
-            if let Some(next_arg) = args_iter.peek() {
-                if !next_arg.starts_with('-') {
-                    filtered_args.push(args_iter.next().unwrap().clone());
-                }
-            }
-        } else if arg.starts_with('-') {
-            filtered_args.push(arg.clone());
-        } else {
-            filtered_commands.push(arg.clone());
-        }
+
+    // Called by the completion scripts, with the words exactly as typed
+    if cli.commands.first().map(String::as_str) == Some(COMPLETE_COMMAND) {
+        handle_complete(&get_executable_name(), &cli.commands[1..]);
+        std::process::exit(0);
     }
 
+    // Only internal commands have switches, and only the first positional
+    // names one: `note self:x` is a value of an API call
+    let internal_tokens = tokenize_with_switches(&cli.commands, INTERNAL_SWITCHES);
+    let is_internal = positionals(&internal_tokens)
+        .first()
+        .is_some_and(|command| command.starts_with("self:"));
+    let tokens = if is_internal {
+        internal_tokens
+    } else {
+        tokenize(&cli.commands)
+    };
+    let filtered_commands = positionals(&tokens);
+
+    let (self_options, flag_tokens) = match extract_self_options(&tokens) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
     let executable_name = get_executable_name();
 
     // Print version
     if filtered_commands.is_empty()
-        && filtered_args
-            .iter()
-            .any(|arg| arg == "-v" || arg == "--version")
+        && flag_tokens.iter().any(|token| match token {
+            Token::Long { key, .. } => key == "version",
+            Token::Short(flags) => flags == "v",
+            Token::Positional(_) => false,
+        })
     {
         println!("anything-cli v{}", version);
         std::process::exit(0);
     }
 
+    let profile = self_options.profile.as_deref();
+    let overrides = self_options.config_overrides();
+
     // Internal commands
     if let Some(cmd) = filtered_commands.first() {
         let must_exit = match cmd.as_str() {
             "self:set-header" => {
-                handle_set_header(&executable_name, &filtered_commands);
+                let secret = has_flag(&flag_tokens, "secret");
+                handle_set_header(&executable_name, &filtered_commands, profile, secret);
                 true
             }
             "self:set-base-url" => {
-                handle_set_base_url(&executable_name, &filtered_commands);
+                handle_set_base_url(&executable_name, &filtered_commands, profile);
+                true
+            }
+            "self:trust-key" => {
+                handle_trust_key(&executable_name, &filtered_commands);
+                true
+            }
+            "self:config" => {
+                let resolved = has_flag(&flag_tokens, "resolved");
+                handle_config(&executable_name, &filtered_commands, resolved, &overrides);
+                true
+            }
+            "self:profile" => {
+                handle_profile(&executable_name, &filtered_commands, profile);
                 true
             }
             "self:uninstall" => {
@@ -94,6 +163,30 @@ fn main() {
                 handle_update(&executable_name);
                 true
             }
+            "self:login" => {
+                handle_login(&executable_name, &overrides);
+                true
+            }
+            "self:logout" => {
+                handle_logout(&executable_name, &overrides);
+                true
+            }
+            "self:manifest" => {
+                handle_manifest(&executable_name, &filtered_commands, &overrides);
+                true
+            }
+            "self:completions" => {
+                handle_completions(&executable_name, &filtered_commands);
+                true
+            }
+            "self:import-openapi" => {
+                handle_import_openapi(&executable_name, &filtered_commands);
+                true
+            }
+            "self:validate" => {
+                handle_validate(&executable_name, &filtered_commands);
+                true
+            }
             _ => false, // Not an internal command
         };
 
@@ -102,65 +195,192 @@ fn main() {
         }
     }
 
-    let (config_option, config_path) = load_config(&executable_name);
-    let config = match config_option {
+    let (mut resolved, config_path) = match load_resolved_config(&executable_name, &overrides) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // Help from the server manifest. A secret or command that can't be
+    // resolved only keeps a stale manifest from being refreshed.
+    if has_flag(&flag_tokens, "help") {
+        if let Some(resolved) = resolved.as_mut() {
+            if is_manifest_stale(&config_path) && apply_credentials(resolved, &config_path).is_ok()
+            {
+                let _ = refresh_manifest(&resolved.config, &config_path, &executable_name);
+            }
+        }
+        let manifest = load_local_manifest(&config_path);
+        let (verb_method, segments) = extract_method_verb(&filtered_commands);
+        let method = self_options.method.clone().or(verb_method);
+        print!(
+            "{}",
+            render_help(
+                &executable_name,
+                manifest.as_ref(),
+                segments,
+                method.as_ref()
+            )
+        );
+        std::process::exit(0);
+    }
+
+    if let Some(resolved) = resolved.as_mut() {
+        if let Err(err) = apply_credentials(resolved, &config_path) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+    let profile_name = resolved.as_ref().map(|resolved| resolved.profile.clone());
+    let mut config = match resolved.map(|resolved| resolved.config) {
         Some(config) => config,
         None => {
             eprintln!(
-                "Failed to load config. Ensure {:?} exists and has the correct format.",
-                &config_path
+                "Failed to load config. Ensure {:?} exists and has the correct format, or set {}.",
+                &config_path,
+                env_var_name(&executable_name, "BASE_URL")
             );
             std::process::exit(1);
         }
     };
-
-    let endpoint = if filtered_commands.is_empty() {
-        format!("{}/", config.base_url)
-    } else {
-        format!("{}/{}", config.base_url, filtered_commands.join("/"))
+    let allow_patterns = match compile_patterns(config.execute.as_ref()) {
+        Ok(patterns) => patterns,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
     };
 
-    let query_params = parse_query_params(&filtered_args);
-    let client = Client::new();
-    let mut request = client.get(&endpoint).query(&query_params);
+    let (verb_method, positional) = extract_method_verb(&filtered_commands);
+    let (path_segments, mut body_items) =
+        match split_body_items(positional, self_options.body_encoding) {
+            Ok(result) => result,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        };
+
+    let mut query_params = query_params_from_tokens(&flag_tokens);
+    let explicit_method = self_options.method.or(verb_method);
 
-    if let Some(headers) = config.headers {
-        for (key, value) in headers {
-            if !key.trim().to_lowercase().starts_with("x-anything-cli-") {
-                request = request.header(&key, &value);
+    // Validate against the cached server manifest before sending anything
+    let manifest = load_local_manifest(&config_path);
+    let command = manifest
+        .as_ref()
+        .and_then(|manifest| match &explicit_method {
+            Some(method) => manifest.find_command(&path_segments, Some(method)),
+            None if !body_items.is_empty() => manifest
+                .find_command(&path_segments, Some(&Method::POST))
+                .or_else(|| manifest.find_command(&path_segments, None)),
+            None => manifest.find_command(&path_segments, None),
+        });
+
+    if let Some(command) = command {
+        let (remaining, routed) = command.route_params(&query_params);
+        query_params = remaining;
+        body_items.extend(routed);
+
+        let errors = command.validate(&path_segments, &query_params, &body_items);
+        if !errors.is_empty() {
+            for error in errors {
+                eprintln!("{}", error);
             }
+            let invocation = match &command.command {
+                Some(_) => path_segments.join(" "),
+                None => format!(
+                    "{} {}",
+                    command.method().as_str().to_lowercase(),
+                    path_segments.join(" ")
+                ),
+            };
+            eprintln!("Run '{} {} --help' for usage.", executable_name, invocation);
+            std::process::exit(1);
         }
     }
 
-    // Headers
-    request = request.header(
-        USER_AGENT,
-        format!(
-            "anything-cli/v{version} ({executable_name}; repo: https://github.com/suchlab/anything-cli)"
-        ),
-    );
-
-    // Add anything-cli headers
-    request = request.header("x-anything-cli-version", version);
-    request = request.header("x-anything-cli-executable-name", executable_name);
-
-    // Add git context headers
-    if let Some((remote_url, repo_name, branch_name)) = get_git_repo_info() {
-        request = request.header("x-anything-cli-git", "true");
-        request = request.header("x-anything-cli-git-repo-url", remote_url);
-        request = request.header("x-anything-cli-git-repo-name", repo_name);
-        request = request.header("x-anything-cli-git-branch", branch_name);
-    }
-
-    let response = match request.send() {
-        Ok(resp) => resp,
+    let mut body = match build_body(&body_items, self_options.body_encoding) {
+        Ok(body) => body,
         Err(err) => {
-            eprintln!("Request failed: {}", err);
+            eprintln!("{}", err);
             std::process::exit(1);
         }
     };
 
+    // Like httpie, sending data without an explicit method means POST
+    let method = match (&explicit_method, command, &body) {
+        (Some(method), _, _) => method.clone(),
+        (None, Some(command), _) => command.method(),
+        (None, None, Some(_)) => Method::POST,
+        (None, None, None) => Method::GET,
+    };
+
+    if body.is_none()
+        && !self_options.ignore_stdin
+        && method != Method::GET
+        && method != Method::HEAD
+    {
+        body = match read_piped_stdin() {
+            Ok(body) => body,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        };
+    }
+
+    let endpoint = match command {
+        Some(command) => endpoint_url(&config.base_url, &command.url_segments(&path_segments)),
+        None => endpoint_url(&config.base_url, &path_segments),
+    };
+
+    let client = Client::new();
+    let array_format = config.query_array_format.unwrap_or_default();
+    // Signed instructions must answer this very request
+    let nonce = config.trusted_key.as_ref().map(|_| new_nonce());
+    let send = |config: &Config| {
+        let mut request = client
+            .request(method.clone(), &endpoint)
+            .query(&query_params.encode(array_format));
+
+        if let Some(body) = body.clone() {
+            request = match apply_body(request, body) {
+                Ok(request) => request,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            };
+        }
+
+        request = apply_default_headers(request, config, &executable_name);
+        if let Some(nonce) = &nonce {
+            request = request.header(NONCE_HEADER, nonce);
+        }
+
+        match request.send() {
+            Ok(resp) => resp,
+            Err(err) => {
+                eprintln!("Request failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let mut response = send(&config);
+
+    // An expired OAuth token is refreshed and the request sent once more
+    if response.status() == StatusCode::UNAUTHORIZED {
+        let profile_name = profile_name.clone().unwrap_or_default();
+        if refresh_after_unauthorized(&mut config, &config_path, &profile_name) {
+            response = send(&config);
+        }
+    }
+
     let response_status = response.status();
+    let response_url = response.url().to_string();
 
     // Handle non-stream response
     let text: String = match response.text() {
@@ -171,18 +391,25 @@ fn main() {
         }
     };
 
-    if let Some(parsed) = parse_anything_schema(&text) {
-        if let Some(instructions) = parsed.instructions {
-            if let Err(exit_code) = process_instructions(&instructions) {
-                std::process::exit(exit_code);
-            }
-        } else {
-            println!("{}", text.trim());
-        }
-    } else if let Ok(json_val) = serde_json::from_str::<serde_json::Value>(&text) {
-        println!("{}", serde_json::to_string(&json_val).unwrap());
-    } else {
-        println!("{}", text.trim());
+    let mut context = InstructionContext {
+        executable_name: executable_name.clone(),
+        config: Some(config),
+        config_path,
+        profile: profile_name.unwrap_or_default(),
+        endpoint,
+        nonce,
+        interactive: io::stdin().is_terminal(),
+        preset_answers: self_options.answers.into_iter().collect(),
+        fail_fast: self_options.fail_fast,
+        dry_run: self_options.dry_run,
+        verbose: self_options.verbose,
+        quiet: self_options.quiet,
+        requests: vec![request_key(&method, &response_url, None)],
+        allow_patterns,
+        ..Default::default()
+    };
+    if let Err(exit_code) = process_response(&text, &mut context) {
+        std::process::exit(exit_code);
     }
 
     // If HTTP status code was not success, exit with error
@@ -0,0 +1,689 @@
+use crate::cli::body::BodyItem;
+use crate::cli::options::parse_method;
+use crate::cli::parse::QueryParams;
+use crate::config::data::{Config, OAuthConfig};
+use crate::request::apply_default_headers;
+use reqwest::blocking::Client;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{fs, io};
+
+pub mod openapi;
+
+/// Endpoint, relative to the base URL, where servers publish their manifest.
+pub const MANIFEST_ENDPOINT: &str = ".anything-cli/manifest";
+const MANIFEST_FILE: &str = "manifest.json";
+const MANIFEST_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    #[default]
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+impl ParamType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParamType::String => "string",
+            ParamType::Integer => "integer",
+            ParamType::Number => "number",
+            ParamType::Boolean => "boolean",
+        }
+    }
+
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            ParamType::String => true,
+            ParamType::Integer => value.parse::<i64>().is_ok(),
+            ParamType::Number => value.parse::<f64>().is_ok(),
+            ParamType::Boolean => value == "true" || value == "false",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamLocation {
+    #[default]
+    Query,
+    Path,
+    Body,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct ParamSpec {
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub param_type: ParamType,
+    #[serde(default, rename = "in")]
+    pub location: ParamLocation,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default, rename = "enum", skip_serializing_if = "Option::is_none")]
+    pub allowed: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct CommandSpec {
+    /// Path relative to the base URL. `{name}` segments are placeholders.
+    pub path: String,
+    /// Words typed to run the command (`users get {id}`), when they differ
+    /// from the path. Placeholders are filled into the path by name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub params: Vec<ParamSpec>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Manifest {
+    pub schema: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub commands: Vec<CommandSpec>,
+    /// Lets `self:login` work without any OAuth settings in the config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth: Option<OAuthConfig>,
+}
+
+fn placeholder(segment: &str) -> Option<&str> {
+    segment.strip_prefix('{')?.strip_suffix('}')
+}
+
+impl ParamSpec {
+    fn check_value(&self, value: &str) -> Option<String> {
+        if !self.param_type.accepts(value) {
+            return Some(format!(
+                "Invalid value for {}: expected {}, got '{}'",
+                self.name,
+                self.param_type.name(),
+                value
+            ));
+        }
+        if let Some(allowed) = &self.allowed {
+            if !allowed.iter().any(|a| a == value) {
+                return Some(format!(
+                    "Invalid value for {}: '{}' (expected one of: {})",
+                    self.name,
+                    value,
+                    allowed.join(", ")
+                ));
+            }
+        }
+        None
+    }
+}
+
+impl CommandSpec {
+    pub fn method(&self) -> Method {
+        self.method
+            .as_deref()
+            .and_then(parse_method)
+            .unwrap_or(Method::GET)
+    }
+
+    /// The segments typed to run the command.
+    pub fn segments(&self) -> Vec<&str> {
+        match &self.command {
+            Some(command) => command.split_whitespace().collect(),
+            None => self.path_segments(),
+        }
+    }
+
+    pub fn path_segments(&self) -> Vec<&str> {
+        self.path
+            .trim_matches('/')
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    /// Builds the URL path segments for a matched invocation.
+    pub fn url_segments(&self, segments: &[String]) -> Vec<String> {
+        let pattern = self.segments();
+        self.path_segments()
+            .iter()
+            .map(|segment| {
+                placeholder(segment)
+                    .and_then(|name| {
+                        pattern
+                            .iter()
+                            .position(|p| placeholder(p) == Some(name))
+                            .and_then(|index| segments.get(index))
+                    })
+                    .cloned()
+                    .unwrap_or_else(|| segment.to_string())
+            })
+            .collect()
+    }
+
+    /// Moves `--name value` flags declared as body params out of the query
+    /// and into body items, typed according to the param.
+    pub fn route_params(&self, query: &QueryParams) -> (QueryParams, Vec<BodyItem>) {
+        let mut remaining = QueryParams::default();
+        let mut body_items = Vec::new();
+
+        for (key, value) in query.iter() {
+            let spec = self
+                .params
+                .iter()
+                .find(|p| p.location == ParamLocation::Body && &p.name == key);
+
+            match spec {
+                Some(spec) if spec.param_type == ParamType::String => {
+                    body_items.push(BodyItem::Field {
+                        key: key.clone(),
+                        value: value.clone(),
+                    })
+                }
+                Some(_) => match serde_json::from_str::<Value>(value) {
+                    Ok(json) => body_items.push(BodyItem::Json {
+                        key: key.clone(),
+                        value: json,
+                    }),
+                    Err(_) => body_items.push(BodyItem::Field {
+                        key: key.clone(),
+                        value: value.clone(),
+                    }),
+                },
+                None => remaining.insert(key.clone(), value.clone()),
+            }
+        }
+
+        (remaining, body_items)
+    }
+
+    /// Number of literal segments matched, or `None` if the path doesn't match.
+    pub fn match_segments(&self, segments: &[String]) -> Option<usize> {
+        let pattern = self.segments();
+        if pattern.len() != segments.len() {
+            return None;
+        }
+
+        let mut literals = 0;
+        for (expected, actual) in pattern.iter().zip(segments) {
+            if placeholder(expected).is_some() {
+                continue;
+            }
+            if expected != actual {
+                return None;
+            }
+            literals += 1;
+        }
+        Some(literals)
+    }
+
+    /// Checks the invocation against the declared parameters and returns every
+    /// problem found, so they can all be reported at once.
+    pub fn validate(
+        &self,
+        segments: &[String],
+        query: &QueryParams,
+        body_items: &[BodyItem],
+    ) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        for (pattern, value) in self.segments().iter().zip(segments) {
+            if let Some(name) = placeholder(pattern) {
+                let spec = self
+                    .params
+                    .iter()
+                    .find(|p| p.location == ParamLocation::Path && p.name == name);
+                if let Some(error) = spec.and_then(|s| s.check_value(value)) {
+                    errors.push(error);
+                }
+            }
+        }
+
+        for (key, value) in query.iter() {
+            match self
+                .params
+                .iter()
+                .find(|p| p.location == ParamLocation::Query && &p.name == key)
+            {
+                Some(spec) => errors.extend(spec.check_value(value)),
+                None => errors.push(format!("Unknown parameter: --{}", key)),
+            }
+        }
+
+        for item in body_items {
+            let (key, value) = match item {
+                BodyItem::Field { key, value } => (key, Some(value.clone())),
+                BodyItem::Json { key, value } => match value {
+                    Value::String(s) => (key, Some(s.clone())),
+                    other => (key, Some(other.to_string())),
+                },
+                BodyItem::File { key, .. } => (key, None),
+                BodyItem::Raw { .. } => continue,
+            };
+            match self
+                .params
+                .iter()
+                .find(|p| p.location == ParamLocation::Body && &p.name == key)
+            {
+                Some(spec) => errors.extend(value.and_then(|v| spec.check_value(&v))),
+                None => errors.push(format!("Unknown body field: {}", key)),
+            }
+        }
+
+        let has_raw_body = body_items.iter().any(|i| matches!(i, BodyItem::Raw { .. }));
+
+        for spec in self.params.iter().filter(|p| p.required) {
+            let present = match spec.location {
+                ParamLocation::Query => query.get(&spec.name).is_some(),
+                ParamLocation::Path => true,
+                ParamLocation::Body => {
+                    has_raw_body
+                        || body_items.iter().any(|item| match item {
+                            BodyItem::Field { key, .. }
+                            | BodyItem::Json { key, .. }
+                            | BodyItem::File { key, .. } => key == &spec.name,
+                            BodyItem::Raw { .. } => false,
+                        })
+                }
+            };
+            if !present {
+                errors.push(match spec.location {
+                    ParamLocation::Body => format!("Missing required body field: {}", spec.name),
+                    _ => format!("Missing required parameter: --{}", spec.name),
+                });
+            }
+        }
+
+        errors
+    }
+}
+
+impl Manifest {
+    /// Finds the command for a path. When no method is given, a `GET` command
+    /// is preferred. Paths with more literal segments win over placeholders.
+    pub fn find_command(
+        &self,
+        segments: &[String],
+        method: Option<&Method>,
+    ) -> Option<&CommandSpec> {
+        self.commands
+            .iter()
+            .filter(|command| match method {
+                Some(method) => command.method() == *method,
+                None => true,
+            })
+            .filter_map(|command| {
+                let literals = command.match_segments(segments)?;
+                let prefers_get = command.method() == Method::GET;
+                Some(((literals, prefers_get), command))
+            })
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, command)| command)
+    }
+
+    /// Adds the commands of another manifest, e.g. one imported from OpenAPI.
+    pub fn merge(&mut self, other: Manifest) {
+        if self.description.is_none() {
+            self.description = other.description;
+        }
+        if self.oauth.is_none() {
+            self.oauth = other.oauth;
+        }
+        self.commands.extend(other.commands);
+    }
+
+    /// Commands whose path starts with the given segments, for help listings.
+    pub fn commands_under(&self, segments: &[String]) -> Vec<&CommandSpec> {
+        self.commands
+            .iter()
+            .filter(|command| {
+                let pattern = command.segments();
+                pattern.len() >= segments.len()
+                    && pattern.iter().zip(segments).all(|(expected, actual)| {
+                        placeholder(expected).is_some() || expected == actual
+                    })
+            })
+            .collect()
+    }
+}
+
+pub fn manifest_cache_path(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .map(|dir| dir.join(MANIFEST_FILE))
+        .unwrap_or_else(|| PathBuf::from(MANIFEST_FILE))
+}
+
+pub fn parse_manifest(json_str: &str) -> Result<Manifest, String> {
+    let manifest: Manifest =
+        serde_json::from_str(json_str).map_err(|e| format!("Invalid manifest: {}", e))?;
+
+    if !manifest.schema.starts_with("anything-cli/v0") {
+        return Err(format!("Unsupported manifest schema: {}", manifest.schema));
+    }
+
+    Ok(manifest)
+}
+
+pub fn load_cached_manifest(config_path: &Path) -> Option<Manifest> {
+    let content = fs::read_to_string(manifest_cache_path(config_path)).ok()?;
+    parse_manifest(&content).ok()
+}
+
+/// The cached server manifest together with any imported OpenAPI commands.
+pub fn load_local_manifest(config_path: &Path) -> Option<Manifest> {
+    let cached = load_cached_manifest(config_path);
+    let imported = openapi::load_openapi_manifest(config_path);
+    match (cached, imported) {
+        (Some(mut manifest), Some(imported)) => {
+            manifest.merge(imported);
+            Some(manifest)
+        }
+        (manifest, imported) => manifest.or(imported),
+    }
+}
+
+pub fn is_manifest_stale(config_path: &Path) -> bool {
+    fs::metadata(manifest_cache_path(config_path))
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|age| age > MANIFEST_MAX_AGE)
+        .unwrap_or(true)
+}
+
+pub fn save_manifest(manifest: &Manifest, config_path: &Path) -> io::Result<()> {
+    let data = serde_json::to_string_pretty(manifest)?;
+    let path = manifest_cache_path(config_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data)
+}
+
+pub fn fetch_manifest(config: &Config, executable_name: &str) -> Result<Manifest, String> {
+    let url = format!("{}/{}", config.base_url, MANIFEST_ENDPOINT);
+    let request = apply_default_headers(Client::new().get(&url), config, executable_name);
+
+    let response = request
+        .send()
+        .map_err(|e| format!("Failed to fetch manifest: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Server returned {} for {}",
+            response.status(),
+            MANIFEST_ENDPOINT
+        ));
+    }
+
+    let text = response
+        .text()
+        .map_err(|e| format!("Failed to read manifest: {}", e))?;
+
+    parse_manifest(&text)
+}
+
+/// Fetches the manifest from the server and caches it next to the config.
+pub fn refresh_manifest(
+    config: &Config,
+    config_path: &Path,
+    executable_name: &str,
+) -> Result<Manifest, String> {
+    let manifest = fetch_manifest(config, executable_name)?;
+    save_manifest(&manifest, config_path)
+        .map_err(|e| format!("Failed to cache manifest: {}", e))?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sample_manifest() -> Manifest {
+        parse_manifest(
+            r#"{
+                "schema": "anything-cli/v0",
+                "commands": [
+                    {
+                        "path": "users",
+                        "description": "List users",
+                        "params": [
+                            {"name": "limit", "type": "integer", "default": 20},
+                            {"name": "status", "enum": ["active", "disabled"]}
+                        ]
+                    },
+                    {
+                        "path": "users",
+                        "method": "POST",
+                        "description": "Create a user",
+                        "params": [
+                            {"name": "name", "in": "body", "required": true},
+                            {"name": "admin", "in": "body", "type": "boolean"}
+                        ]
+                    },
+                    {
+                        "path": "users/{id}",
+                        "description": "Get a user",
+                        "params": [{"name": "id", "in": "path", "type": "integer"}]
+                    },
+                    {
+                        "path": "users/me",
+                        "description": "Get the current user"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn query(pairs: &[(&str, &str)]) -> QueryParams {
+        let mut params = QueryParams::default();
+        for (key, value) in pairs {
+            params.insert(key.to_string(), value.to_string());
+        }
+        params
+    }
+
+    #[test]
+    fn test_parse_manifest_rejects_other_schema() {
+        assert!(parse_manifest(r#"{"schema": "other/v1", "commands": []}"#).is_err());
+        assert!(parse_manifest("{ invalid json }").is_err());
+    }
+
+    #[test]
+    fn test_find_command_prefers_get() {
+        let manifest = sample_manifest();
+        let command = manifest.find_command(&to_args(&["users"]), None).unwrap();
+        assert_eq!(command.description.as_deref(), Some("List users"));
+
+        let command = manifest
+            .find_command(&to_args(&["users"]), Some(&Method::POST))
+            .unwrap();
+        assert_eq!(command.description.as_deref(), Some("Create a user"));
+    }
+
+    #[test]
+    fn test_find_command_prefers_literals() {
+        let manifest = sample_manifest();
+        let command = manifest
+            .find_command(&to_args(&["users", "me"]), None)
+            .unwrap();
+        assert_eq!(command.path, "users/me");
+
+        let command = manifest
+            .find_command(&to_args(&["users", "42"]), None)
+            .unwrap();
+        assert_eq!(command.path, "users/{id}");
+
+        assert!(manifest.find_command(&to_args(&["posts"]), None).is_none());
+    }
+
+    #[test]
+    fn test_validate_unknown_and_invalid_params() {
+        let manifest = sample_manifest();
+        let segments = to_args(&["users"]);
+        let command = manifest.find_command(&segments, None).unwrap();
+
+        let errors = command.validate(
+            &segments,
+            &query(&[("limit", "ten"), ("status", "gone"), ("sort", "name")]),
+            &[],
+        );
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("limit"));
+        assert!(errors[1].contains("active, disabled"));
+        assert_eq!(errors[2], "Unknown parameter: --sort");
+
+        assert!(command
+            .validate(&segments, &query(&[("limit", "10")]), &[])
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_required_body_field() {
+        let manifest = sample_manifest();
+        let segments = to_args(&["users"]);
+        let command = manifest
+            .find_command(&segments, Some(&Method::POST))
+            .unwrap();
+
+        let errors = command.validate(&segments, &QueryParams::default(), &[]);
+        assert_eq!(errors, vec!["Missing required body field: name"]);
+
+        let items = vec![
+            BodyItem::Field {
+                key: "name".to_string(),
+                value: "John".to_string(),
+            },
+            BodyItem::Field {
+                key: "admin".to_string(),
+                value: "yes".to_string(),
+            },
+        ];
+        let errors = command.validate(&segments, &QueryParams::default(), &items);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("admin"));
+    }
+
+    #[test]
+    fn test_validate_path_param_type() {
+        let manifest = sample_manifest();
+        let segments = to_args(&["users", "abc"]);
+        let command = manifest.find_command(&segments, None).unwrap();
+        let errors = command.validate(&segments, &QueryParams::default(), &[]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("id"));
+    }
+
+    #[test]
+    fn test_command_words_map_to_path() {
+        let manifest = parse_manifest(
+            r#"{
+                "schema": "anything-cli/v0",
+                "commands": [
+                    {"path": "orgs/{org}/repos/{repo}", "command": "repos get {org} {repo}"},
+                    {"path": "orgs/{org}/repos", "command": "repos create {org}", "method": "POST",
+                     "params": [{"name": "private", "in": "body", "type": "boolean"},
+                                {"name": "name", "in": "body"}]}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let segments = to_args(&["repos", "get", "acme", "cli"]);
+        let command = manifest.find_command(&segments, None).unwrap();
+        assert_eq!(
+            command.url_segments(&segments),
+            to_args(&["orgs", "acme", "repos", "cli"])
+        );
+
+        let segments = to_args(&["repos", "create", "acme"]);
+        let command = manifest.find_command(&segments, None).unwrap();
+        assert_eq!(command.method(), Method::POST);
+        assert_eq!(
+            command.url_segments(&segments),
+            to_args(&["orgs", "acme", "repos"])
+        );
+    }
+
+    #[test]
+    fn test_route_params_moves_body_flags() {
+        let command = CommandSpec {
+            path: "users".to_string(),
+            method: Some("POST".to_string()),
+            params: vec![
+                ParamSpec {
+                    name: "name".to_string(),
+                    location: ParamLocation::Body,
+                    ..Default::default()
+                },
+                ParamSpec {
+                    name: "age".to_string(),
+                    param_type: ParamType::Integer,
+                    location: ParamLocation::Body,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let (remaining, items) =
+            command.route_params(&query(&[("name", "John"), ("age", "30"), ("dry", "true")]));
+        assert_eq!(remaining, query(&[("dry", "true")]));
+        assert_eq!(
+            items,
+            vec![
+                BodyItem::Field {
+                    key: "name".to_string(),
+                    value: "John".to_string()
+                },
+                BodyItem::Json {
+                    key: "age".to_string(),
+                    value: serde_json::json!(30)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_commands_under() {
+        let manifest = sample_manifest();
+        assert_eq!(manifest.commands_under(&[]).len(), 4);
+        assert_eq!(manifest.commands_under(&to_args(&["users", "me"])).len(), 2);
+        assert!(manifest.commands_under(&to_args(&["posts"])).is_empty());
+    }
+
+    #[test]
+    fn test_manifest_cache_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join(".test-app").join("config.json");
+
+        assert!(load_cached_manifest(&config_path).is_none());
+        assert!(is_manifest_stale(&config_path));
+
+        let manifest = sample_manifest();
+        save_manifest(&manifest, &config_path).unwrap();
+
+        assert_eq!(load_cached_manifest(&config_path), Some(manifest));
+        assert!(!is_manifest_stale(&config_path));
+        assert!(manifest_cache_path(&config_path).ends_with(".test-app/manifest.json"));
+    }
+}
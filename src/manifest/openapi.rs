@@ -0,0 +1,428 @@
+use crate::manifest::{CommandSpec, Manifest, ParamLocation, ParamSpec, ParamType};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const OPENAPI_FILE: &str = "openapi.json";
+const METHODS: &[&str] = &["get", "post", "put", "patch", "delete", "head", "options"];
+
+pub fn openapi_path(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .map(|dir| dir.join(OPENAPI_FILE))
+        .unwrap_or_else(|| PathBuf::from(OPENAPI_FILE))
+}
+
+/// Parses an OpenAPI 3 document written in JSON or YAML.
+pub fn parse_openapi(content: &str) -> Result<Value, String> {
+    let spec: Value = match serde_json::from_str(content) {
+        Ok(spec) => spec,
+        Err(_) => {
+            serde_yaml::from_str(content).map_err(|e| format!("Invalid OpenAPI document: {}", e))?
+        }
+    };
+
+    match spec.get("openapi").and_then(Value::as_str) {
+        Some(version) if version.starts_with("3.") => Ok(spec),
+        Some(version) => Err(format!("Unsupported OpenAPI version: {}", version)),
+        None => Err("Not an OpenAPI 3 document: missing 'openapi' field".to_string()),
+    }
+}
+
+/// Follows a local `$ref` such as `#/components/schemas/User`.
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    let mut current = value;
+    // Bounded to avoid looping on self-referencing documents
+    for _ in 0..16 {
+        let Some(reference) = current.get("$ref").and_then(Value::as_str) else {
+            return current;
+        };
+        let Some(pointer) = reference.strip_prefix('#') else {
+            return current;
+        };
+        match spec.pointer(pointer) {
+            Some(target) => current = target,
+            None => return current,
+        }
+    }
+    current
+}
+
+fn param_type(schema: &Value) -> ParamType {
+    match schema.get("type").and_then(Value::as_str) {
+        Some("integer") => ParamType::Integer,
+        Some("number") => ParamType::Number,
+        Some("boolean") => ParamType::Boolean,
+        _ => ParamType::String,
+    }
+}
+
+fn enum_values(schema: &Value) -> Option<Vec<String>> {
+    schema.get("enum").and_then(Value::as_array).map(|values| {
+        values
+            .iter()
+            .map(|v| match v {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect()
+    })
+}
+
+fn param_from_schema(name: &str, schema: &Value, location: ParamLocation) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        param_type: param_type(schema),
+        location,
+        required: location == ParamLocation::Path,
+        allowed: enum_values(schema),
+        default: schema.get("default").cloned(),
+        description: schema
+            .get("description")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    }
+}
+
+fn operation_params(spec: &Value, path_item: &Value, operation: &Value) -> Vec<ParamSpec> {
+    let mut params: Vec<ParamSpec> = Vec::new();
+
+    let declared = path_item
+        .get("parameters")
+        .and_then(Value::as_array)
+        .into_iter()
+        .chain(operation.get("parameters").and_then(Value::as_array))
+        .flatten();
+
+    for param in declared {
+        let param = resolve(spec, param);
+        let Some(name) = param.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let location = match param.get("in").and_then(Value::as_str) {
+            Some("query") => ParamLocation::Query,
+            Some("path") => ParamLocation::Path,
+            // Headers and cookies aren't set from the command line
+            _ => continue,
+        };
+
+        let schema = param
+            .get("schema")
+            .map(|schema| resolve(spec, schema))
+            .unwrap_or(&Value::Null);
+        let mut spec_param = param_from_schema(name, schema, location);
+        spec_param.required |= param
+            .get("required")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        if let Some(description) = param.get("description").and_then(Value::as_str) {
+            spec_param.description = Some(description.to_string());
+        }
+
+        // Operation-level parameters override path-level ones
+        params.retain(|p| !(p.name == spec_param.name && p.location == spec_param.location));
+        params.push(spec_param);
+    }
+
+    let body_schema = operation
+        .get("requestBody")
+        .map(|body| resolve(spec, body))
+        .and_then(|body| body.pointer("/content/application~1json/schema"))
+        .map(|schema| resolve(spec, schema));
+
+    if let Some(schema) = body_schema {
+        let required: HashSet<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|names| names.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let empty = Map::new();
+        let properties = schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+
+        for (name, property) in properties {
+            let property = resolve(spec, property);
+            let mut param = param_from_schema(name, property, ParamLocation::Body);
+            param.required = required.contains(name.as_str());
+            params.push(param);
+        }
+    }
+
+    params
+}
+
+/// Picks the command verb: `list`/`get` for reads, `create`, `update`,
+/// `replace` and `delete` for writes.
+fn command_verb(method: &str, ends_with_placeholder: bool, has_patch: bool) -> &'static str {
+    match method {
+        "get" if ends_with_placeholder => "get",
+        "get" => "list",
+        "post" => "create",
+        "put" if has_patch => "replace",
+        "put" | "patch" => "update",
+        "delete" => "delete",
+        "head" => "head",
+        _ => "options",
+    }
+}
+
+fn kebab_case(value: &str) -> String {
+    let mut output = String::new();
+    for (i, c) in value.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                output.push('-');
+            }
+            output.extend(c.to_lowercase());
+        } else if c == '_' || c == ' ' {
+            output.push('-');
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+/// Maps every operation to a command. `/users/{id}` with `GET` becomes
+/// `users get {id}` and `POST /users` becomes `users create`. An operation can
+/// pick its own words with the `x-anything-cli-command` extension.
+pub fn manifest_from_openapi(spec: &Value) -> Manifest {
+    let mut commands: Vec<CommandSpec> = Vec::new();
+    let empty = Map::new();
+    let paths = spec
+        .get("paths")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+
+    for (path, path_item) in paths {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let literals: Vec<&str> = segments
+            .iter()
+            .copied()
+            .filter(|s| !s.starts_with('{'))
+            .collect();
+        let placeholders: Vec<&str> = segments
+            .iter()
+            .copied()
+            .filter(|s| s.starts_with('{'))
+            .collect();
+        let ends_with_placeholder = segments.last().is_some_and(|s| s.starts_with('{'));
+        let has_patch = path_item.get("patch").is_some();
+
+        for method in METHODS {
+            let Some(operation) = path_item.get(*method) else {
+                continue;
+            };
+
+            let words = match operation
+                .get("x-anything-cli-command")
+                .and_then(Value::as_str)
+            {
+                Some(words) => words.to_string(),
+                None => {
+                    let mut words = literals.clone();
+                    words.push(command_verb(method, ends_with_placeholder, has_patch));
+                    words.extend(placeholders.iter().copied());
+                    words.join(" ")
+                }
+            };
+
+            let words = if commands
+                .iter()
+                .any(|c| c.command.as_deref() == Some(&words))
+            {
+                match operation.get("operationId").and_then(Value::as_str) {
+                    Some(id) => {
+                        let mut words = literals.clone();
+                        let id = kebab_case(id);
+                        words.push(&id);
+                        words.extend(placeholders.iter().copied());
+                        words.join(" ")
+                    }
+                    None => continue,
+                }
+            } else {
+                words
+            };
+
+            let description = operation
+                .get("summary")
+                .or_else(|| operation.get("description"))
+                .and_then(Value::as_str)
+                .map(str::to_string);
+
+            commands.push(CommandSpec {
+                path: path.trim_matches('/').to_string(),
+                command: Some(words),
+                method: Some(method.to_uppercase()),
+                description,
+                params: operation_params(spec, path_item, operation),
+            });
+        }
+    }
+
+    Manifest {
+        schema: "anything-cli/v0".to_string(),
+        description: spec
+            .pointer("/info/title")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        commands,
+        ..Default::default()
+    }
+}
+
+pub fn load_openapi_manifest(config_path: &Path) -> Option<Manifest> {
+    let content = fs::read_to_string(openapi_path(config_path)).ok()?;
+    let spec = parse_openapi(&content).ok()?;
+    Some(manifest_from_openapi(&spec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_spec() -> Value {
+        json!({
+            "openapi": "3.0.3",
+            "info": {"title": "Users API"},
+            "paths": {
+                "/users": {
+                    "get": {
+                        "summary": "List users",
+                        "parameters": [
+                            {"name": "limit", "in": "query", "schema": {"type": "integer", "default": 20}},
+                            {"name": "X-Trace", "in": "header", "schema": {"type": "string"}}
+                        ]
+                    },
+                    "post": {
+                        "summary": "Create a user",
+                        "requestBody": {"$ref": "#/components/requestBodies/NewUser"}
+                    }
+                },
+                "/users/{id}": {
+                    "parameters": [
+                        {"$ref": "#/components/parameters/UserId"}
+                    ],
+                    "get": {"summary": "Get a user"},
+                    "put": {"summary": "Replace a user"},
+                    "patch": {"summary": "Update a user"},
+                    "delete": {"operationId": "deleteUser"}
+                },
+                "/users/{id}/avatar": {
+                    "get": {"summary": "Get avatar", "x-anything-cli-command": "avatar {id}"}
+                }
+            },
+            "components": {
+                "parameters": {
+                    "UserId": {"name": "id", "in": "path", "required": true, "schema": {"type": "integer"}}
+                },
+                "requestBodies": {
+                    "NewUser": {
+                        "content": {"application/json": {"schema": {"$ref": "#/components/schemas/NewUser"}}}
+                    }
+                },
+                "schemas": {
+                    "NewUser": {
+                        "type": "object",
+                        "required": ["name"],
+                        "properties": {
+                            "name": {"type": "string", "description": "Full name"},
+                            "role": {"type": "string", "enum": ["admin", "member"]},
+                            "age": {"type": "integer"}
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    fn find<'a>(manifest: &'a Manifest, words: &str) -> &'a CommandSpec {
+        manifest
+            .commands
+            .iter()
+            .find(|c| c.command.as_deref() == Some(words))
+            .unwrap_or_else(|| panic!("Missing command: {}", words))
+    }
+
+    #[test]
+    fn test_parse_openapi_json_and_yaml() {
+        assert!(parse_openapi(r#"{"openapi": "3.1.0", "paths": {}}"#).is_ok());
+        assert!(parse_openapi("openapi: 3.0.0\npaths: {}\n").is_ok());
+        assert!(parse_openapi(r#"{"swagger": "2.0"}"#).is_err());
+        assert!(parse_openapi(r#"{"openapi": "2.0"}"#).is_err());
+    }
+
+    #[test]
+    fn test_manifest_from_openapi_command_words() {
+        let manifest = manifest_from_openapi(&sample_spec());
+        assert_eq!(manifest.description.as_deref(), Some("Users API"));
+        assert_eq!(manifest.commands.len(), 7);
+
+        assert_eq!(find(&manifest, "users list").method.as_deref(), Some("GET"));
+        assert_eq!(
+            find(&manifest, "users create").method.as_deref(),
+            Some("POST")
+        );
+        assert_eq!(find(&manifest, "users get {id}").path, "users/{id}");
+        assert_eq!(
+            find(&manifest, "users replace {id}").method.as_deref(),
+            Some("PUT")
+        );
+        assert_eq!(
+            find(&manifest, "users update {id}").method.as_deref(),
+            Some("PATCH")
+        );
+        assert_eq!(
+            find(&manifest, "users delete {id}").method.as_deref(),
+            Some("DELETE")
+        );
+        assert_eq!(find(&manifest, "avatar {id}").path, "users/{id}/avatar");
+    }
+
+    #[test]
+    fn test_manifest_from_openapi_params() {
+        let manifest = manifest_from_openapi(&sample_spec());
+
+        let list = find(&manifest, "users list");
+        assert_eq!(list.description.as_deref(), Some("List users"));
+        assert_eq!(list.params.len(), 1);
+        assert_eq!(list.params[0].param_type, ParamType::Integer);
+        assert_eq!(list.params[0].default, Some(json!(20)));
+
+        let get = find(&manifest, "users get {id}");
+        assert_eq!(get.params[0].location, ParamLocation::Path);
+        assert!(get.params[0].required);
+
+        let create = find(&manifest, "users create");
+        let name = create.params.iter().find(|p| p.name == "name").unwrap();
+        assert_eq!(name.location, ParamLocation::Body);
+        assert!(name.required);
+        assert_eq!(name.description.as_deref(), Some("Full name"));
+        let role = create.params.iter().find(|p| p.name == "role").unwrap();
+        assert_eq!(
+            role.allowed,
+            Some(vec!["admin".to_string(), "member".to_string()])
+        );
+        assert!(!role.required);
+    }
+
+    #[test]
+    fn test_manifest_from_openapi_duplicate_words_use_operation_id() {
+        let spec = json!({
+            "openapi": "3.0.0",
+            "paths": {
+                "/users": {"get": {"summary": "List users"}},
+                "/users/": {"get": {"operationId": "listUsersLegacy"}}
+            }
+        });
+        let manifest = manifest_from_openapi(&spec);
+        assert_eq!(manifest.commands.len(), 2);
+        find(&manifest, "users list");
+        find(&manifest, "users list-users-legacy");
+    }
+}
@@ -0,0 +1,483 @@
+use crate::config::data::{Config, OAuthConfig};
+use crate::manifest::load_local_manifest;
+use crate::request::{resolve_url, same_origin};
+use crate::secrets::{secret_name, SecretStore};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// Tokens are refreshed a little before they expire, so a request sent right
+/// at the end of their lifetime doesn't fail.
+const EXPIRY_MARGIN_SECS: u64 = 30;
+/// How long to wait for approval when the server doesn't say, as RFC 8628
+/// suggests.
+const DEFAULT_DEVICE_CODE_LIFETIME_SECS: u64 = 900;
+/// Longest wait between polls, however often the server asks to slow down.
+const MAX_POLL_INTERVAL_SECS: u64 = 60;
+/// Stored under a name no header can have, see `secret_name`.
+const TOKENS_SECRET: &str = "@oauth";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OAuthTokens {
+    pub access_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// Unix timestamp, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    #[serde(default)]
+    pub interval: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl OAuthTokens {
+    fn from_response(response: TokenResponse, previous_refresh: Option<String>) -> OAuthTokens {
+        OAuthTokens {
+            access_token: response.access_token,
+            // Servers may keep the refresh token unchanged and not send it again
+            refresh_token: response.refresh_token.or(previous_refresh),
+            expires_at: response.expires_in.map(|secs| now() + secs),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| now() + EXPIRY_MARGIN_SECS >= expires_at)
+    }
+}
+
+/// The first endpoint of server-given OAuth settings that isn't on the base
+/// URL's origin, if any.
+fn foreign_endpoint<'a>(oauth: &'a OAuthConfig, base_url: &str) -> Option<&'a str> {
+    [&oauth.device_authorization_endpoint, &oauth.token_endpoint]
+        .into_iter()
+        .find(|endpoint| !same_origin(endpoint, base_url))
+        .map(String::as_str)
+}
+
+/// OAuth settings from the config, else from the server manifest. The
+/// manifest's endpoints must be on the API's origin.
+pub fn oauth_settings(config: &Config, config_path: &Path) -> Option<OAuthConfig> {
+    if let Some(oauth) = &config.oauth {
+        return Some(oauth.clone());
+    }
+    let oauth = load_local_manifest(config_path)?.oauth?;
+    if let Some(endpoint) = foreign_endpoint(&oauth, &config.base_url) {
+        eprintln!(
+            "Ignoring the OAuth settings of the server manifest: {} is not on {}",
+            endpoint, config.base_url
+        );
+        return None;
+    }
+    Some(oauth)
+}
+
+fn token_error(body: &Value) -> Option<&str> {
+    body.get("error").and_then(Value::as_str)
+}
+
+pub fn request_device_authorization(
+    oauth: &OAuthConfig,
+    base_url: &str,
+) -> Result<DeviceAuthorization, String> {
+    let mut form = vec![("client_id", oauth.client_id.as_str())];
+    if let Some(scope) = &oauth.scope {
+        form.push(("scope", scope));
+    }
+
+    let response = Client::new()
+        .post(resolve_url(base_url, &oauth.device_authorization_endpoint))
+        .form(&form)
+        .send()
+        .map_err(|e| format!("Device authorization request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Device authorization failed with status {}",
+            response.status()
+        ));
+    }
+
+    response
+        .json()
+        .map_err(|e| format!("Invalid device authorization response: {}", e))
+}
+
+fn poll_interval(secs: u64) -> Duration {
+    Duration::from_secs(secs.min(MAX_POLL_INTERVAL_SECS))
+}
+
+/// Polls the token endpoint until the user approves or denies the request,
+/// or the device code expires.
+pub fn poll_for_tokens(
+    oauth: &OAuthConfig,
+    base_url: &str,
+    device: &DeviceAuthorization,
+) -> Result<OAuthTokens, String> {
+    let client = Client::new();
+    let url = resolve_url(base_url, &oauth.token_endpoint);
+    let mut interval = poll_interval(device.interval.unwrap_or(5));
+    let deadline = now()
+        + device
+            .expires_in
+            .unwrap_or(DEFAULT_DEVICE_CODE_LIFETIME_SECS);
+
+    loop {
+        if now() >= deadline {
+            return Err("The login request expired. Run self:login again.".to_string());
+        }
+
+        let response = client
+            .post(&url)
+            .form(&[
+                ("grant_type", DEVICE_CODE_GRANT),
+                ("device_code", &device.device_code),
+                ("client_id", &oauth.client_id),
+            ])
+            .send()
+            .map_err(|e| format!("Token request failed: {}", e))?;
+
+        let success = response.status().is_success();
+        let body: Value = response
+            .json()
+            .map_err(|e| format!("Invalid token response: {}", e))?;
+
+        if success {
+            let token: TokenResponse = serde_json::from_value(body)
+                .map_err(|e| format!("Invalid token response: {}", e))?;
+            return Ok(OAuthTokens::from_response(token, None));
+        }
+
+        match token_error(&body) {
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval = poll_interval(interval.as_secs() + 5),
+            Some("access_denied") => return Err("Login was denied.".to_string()),
+            Some("expired_token") => {
+                return Err("The login request expired. Run self:login again.".to_string())
+            }
+            Some(other) => return Err(format!("Login failed: {}", other)),
+            None => return Err("Login failed: unexpected token response".to_string()),
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+pub fn refresh_tokens(
+    oauth: &OAuthConfig,
+    base_url: &str,
+    tokens: &OAuthTokens,
+) -> Result<OAuthTokens, String> {
+    let refresh_token = tokens
+        .refresh_token
+        .as_deref()
+        .ok_or("No refresh token stored. Run self:login again.")?;
+
+    let response = Client::new()
+        .post(resolve_url(base_url, &oauth.token_endpoint))
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", &oauth.client_id),
+        ])
+        .send()
+        .map_err(|e| format!("Token refresh failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Token refresh failed with status {}. Run self:login again.",
+            response.status()
+        ));
+    }
+
+    let token: TokenResponse = response
+        .json()
+        .map_err(|e| format!("Invalid token response: {}", e))?;
+    Ok(OAuthTokens::from_response(
+        token,
+        tokens.refresh_token.clone(),
+    ))
+}
+
+pub fn load_tokens(config_path: &Path, profile: &str) -> Option<OAuthTokens> {
+    let store = SecretStore::open(config_path).ok()?;
+    let data = store.get(&secret_name(profile, TOKENS_SECRET))?;
+    serde_json::from_str(data).ok()
+}
+
+pub fn save_tokens(config_path: &Path, profile: &str, tokens: &OAuthTokens) -> Result<(), String> {
+    let mut store = SecretStore::open(config_path)?;
+    let data = serde_json::to_string(tokens).map_err(|e| e.to_string())?;
+    store.set(&secret_name(profile, TOKENS_SECRET), &data);
+    store.save()
+}
+
+/// Removes the stored tokens, returning whether there were any.
+pub fn clear_tokens(config_path: &Path, profile: &str) -> Result<bool, String> {
+    let mut store = SecretStore::open(config_path)?;
+    let removed = store.remove(&secret_name(profile, TOKENS_SECRET));
+    if removed {
+        store.save()?;
+    }
+    Ok(removed)
+}
+
+fn has_authorization(config: &Config) -> bool {
+    config
+        .headers
+        .iter()
+        .flatten()
+        .any(|(key, _)| key.eq_ignore_ascii_case("authorization"))
+}
+
+fn set_bearer(config: &mut Config, access_token: &str) {
+    let headers = config.headers.get_or_insert_with(Default::default);
+    headers.retain(|key, _| !key.eq_ignore_ascii_case("authorization"));
+    headers.insert(
+        "Authorization".to_string(),
+        format!("Bearer {}", access_token),
+    );
+}
+
+/// Sends the stored access token as `Authorization`, unless a header already
+/// sets one. An expired token is refreshed first when possible.
+pub fn apply_stored_token(config: &mut Config, config_path: &Path, profile: &str) {
+    if has_authorization(config) {
+        return;
+    }
+    let Some(mut tokens) = load_tokens(config_path, profile) else {
+        return;
+    };
+
+    if tokens.is_expired() {
+        if let Some(oauth) = oauth_settings(config, config_path) {
+            if let Ok(refreshed) = refresh_tokens(&oauth, &config.base_url, &tokens) {
+                let _ = save_tokens(config_path, profile, &refreshed);
+                tokens = refreshed;
+            }
+        }
+    }
+
+    set_bearer(config, &tokens.access_token);
+}
+
+/// Called after a 401. If the rejected token is the stored one, refreshes
+/// it and updates `config`, returning whether the request should be retried.
+pub fn refresh_after_unauthorized(config: &mut Config, config_path: &Path, profile: &str) -> bool {
+    let Some(tokens) = load_tokens(config_path, profile) else {
+        return false;
+    };
+    let sent_stored_token = config.headers.iter().flatten().any(|(key, value)| {
+        key.eq_ignore_ascii_case("authorization")
+            && *value == format!("Bearer {}", tokens.access_token)
+    });
+    if !sent_stored_token {
+        return false;
+    }
+
+    let Some(oauth) = oauth_settings(config, config_path) else {
+        return false;
+    };
+    match refresh_tokens(&oauth, &config.base_url, &tokens) {
+        Ok(refreshed) => {
+            let _ = save_tokens(config_path, profile, &refreshed);
+            set_bearer(config, &refreshed.access_token);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_server::serve;
+    use tempfile::TempDir;
+
+    fn oauth_config() -> OAuthConfig {
+        OAuthConfig {
+            client_id: "cli".to_string(),
+            device_authorization_endpoint: "device".to_string(),
+            token_endpoint: "/token".to_string(),
+            scope: Some("read".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_poll_interval_is_capped() {
+        assert_eq!(poll_interval(5), Duration::from_secs(5));
+        assert_eq!(poll_interval(58 + 5), Duration::from_secs(60));
+        assert_eq!(poll_interval(u64::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_device_flow() {
+        let (url, requests) = serve(vec![
+            (
+                200,
+                r#"{"device_code": "dev-1", "user_code": "ABCD-EFGH",
+                    "verification_uri": "https://example.com/device", "interval": 0}"#,
+            ),
+            (400, r#"{"error": "authorization_pending"}"#),
+            (
+                200,
+                r#"{"access_token": "at-1", "refresh_token": "rt-1", "expires_in": 3600}"#,
+            ),
+        ]);
+
+        let oauth = oauth_config();
+        let device = request_device_authorization(&oauth, &url).unwrap();
+        assert_eq!(device.user_code, "ABCD-EFGH");
+
+        let tokens = poll_for_tokens(&oauth, &url, &device).unwrap();
+        assert_eq!(tokens.access_token, "at-1");
+        assert_eq!(tokens.refresh_token.as_deref(), Some("rt-1"));
+        assert!(!tokens.is_expired());
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].body, "client_id=cli&scope=read");
+        assert!(requests[1].body.contains("device_code=dev-1"));
+        assert!(requests[1]
+            .body
+            .contains("grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Adevice_code"));
+    }
+
+    #[test]
+    fn test_manifest_endpoints_stay_on_origin() {
+        let base_url = "https://api.example.com";
+        assert_eq!(foreign_endpoint(&oauth_config(), base_url), None);
+
+        let oauth = OAuthConfig {
+            token_endpoint: "https://api.example.com.evil.io/token".to_string(),
+            ..oauth_config()
+        };
+        assert_eq!(
+            foreign_endpoint(&oauth, base_url),
+            Some("https://api.example.com.evil.io/token")
+        );
+    }
+
+    #[test]
+    fn test_device_flow_denied() {
+        let (url, _) = serve(vec![(400, r#"{"error": "access_denied"}"#)]);
+        let device = DeviceAuthorization {
+            device_code: "dev-1".to_string(),
+            user_code: "ABCD".to_string(),
+            verification_uri: "https://example.com/device".to_string(),
+            verification_uri_complete: None,
+            expires_in: None,
+            interval: Some(0),
+        };
+        assert_eq!(
+            poll_for_tokens(&oauth_config(), &url, &device).unwrap_err(),
+            "Login was denied."
+        );
+    }
+
+    #[test]
+    fn test_refresh_after_unauthorized() {
+        let (url, requests) = serve(vec![(200, r#"{"access_token": "at-2"}"#)]);
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let tokens = OAuthTokens {
+            access_token: "at-1".to_string(),
+            refresh_token: Some("rt-1".to_string()),
+            expires_at: None,
+        };
+        save_tokens(&config_path, "default", &tokens).unwrap();
+
+        let mut config = Config {
+            base_url: url,
+            oauth: Some(oauth_config()),
+            ..Default::default()
+        };
+        apply_stored_token(&mut config, &config_path, "default");
+        assert_eq!(
+            config.headers.as_ref().unwrap()["Authorization"],
+            "Bearer at-1"
+        );
+
+        assert!(refresh_after_unauthorized(
+            &mut config,
+            &config_path,
+            "default"
+        ));
+        assert_eq!(
+            config.headers.as_ref().unwrap()["Authorization"],
+            "Bearer at-2"
+        );
+        assert!(requests.lock().unwrap()[0]
+            .body
+            .contains("refresh_token=rt-1"));
+
+        // The refresh token is kept when the server doesn't send a new one
+        let stored = load_tokens(&config_path, "default").unwrap();
+        assert_eq!(stored.access_token, "at-2");
+        assert_eq!(stored.refresh_token.as_deref(), Some("rt-1"));
+
+        assert!(clear_tokens(&config_path, "default").unwrap());
+        assert!(load_tokens(&config_path, "default").is_none());
+    }
+
+    #[test]
+    fn test_configured_authorization_wins() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        let tokens = OAuthTokens {
+            access_token: "at-1".to_string(),
+            refresh_token: None,
+            expires_at: None,
+        };
+        save_tokens(&config_path, "default", &tokens).unwrap();
+
+        let mut config = Config {
+            headers: Some(
+                [("authorization".to_string(), "Token abc".to_string())]
+                    .into_iter()
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        apply_stored_token(&mut config, &config_path, "default");
+        assert_eq!(
+            config.headers.as_ref().unwrap()["authorization"],
+            "Token abc"
+        );
+        assert!(!refresh_after_unauthorized(
+            &mut config,
+            &config_path,
+            "default"
+        ));
+    }
+}
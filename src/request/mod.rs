@@ -0,0 +1,157 @@
+use crate::config::data::Config;
+use crate::schema::supported_schemas;
+use crate::utils::git::get_git_repo_info;
+use reqwest::blocking::RequestBuilder;
+use reqwest::header::USER_AGENT;
+use reqwest::Url;
+
+pub fn endpoint_url(base_url: &str, segments: &[String]) -> String {
+    if segments.is_empty() {
+        format!("{}/", base_url)
+    } else {
+        format!("{}/{}", base_url, segments.join("/"))
+    }
+}
+
+/// Server-given URLs may be absolute or relative to the base URL.
+pub fn resolve_url(base_url: &str, url: &str) -> String {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        url.to_string()
+    } else {
+        format!(
+            "{}/{}",
+            base_url.trim_end_matches('/'),
+            url.trim_start_matches('/')
+        )
+    }
+}
+
+/// Whether `url` has the scheme, host and port of `base_url`, so that it can
+/// be sent credentials. Relative URLs are resolved against `base_url`.
+pub fn same_origin(url: &str, base_url: &str) -> bool {
+    let url = resolve_url(base_url, url);
+    match (Url::parse(&url), Url::parse(base_url)) {
+        (Ok(url), Ok(base)) => url.origin() == base.origin(),
+        _ => false,
+    }
+}
+
+/// Adds the configured headers plus the anything-cli context headers that
+/// every request sent by the CLI carries.
+pub fn apply_default_headers(
+    mut request: RequestBuilder,
+    config: &Config,
+    executable_name: &str,
+) -> RequestBuilder {
+    let version = env!("CARGO_PKG_VERSION");
+
+    if let Some(headers) = &config.headers {
+        for (key, value) in headers {
+            if !key.trim().to_lowercase().starts_with("x-anything-cli-") {
+                request = request.header(key, value);
+            }
+        }
+    }
+
+    // Headers
+    request = request.header(
+        USER_AGENT,
+        format!(
+            "anything-cli/v{version} ({executable_name}; repo: https://github.com/suchlab/anything-cli)"
+        ),
+    );
+
+    // Add anything-cli headers
+    request = request.header("x-anything-cli-version", version);
+    request = request.header("x-anything-cli-executable-name", executable_name);
+    request = request.header("x-anything-cli-schema", supported_schemas());
+
+    // Add git context headers
+    if let Some((remote_url, repo_name, branch_name)) = get_git_repo_info() {
+        request = request.header("x-anything-cli-git", "true");
+        request = request.header("x-anything-cli-git-repo-url", remote_url);
+        request = request.header("x-anything-cli-git-repo-name", repo_name);
+        request = request.header("x-anything-cli-git-branch", branch_name);
+    }
+
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::blocking::Client;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_endpoint_url_root() {
+        assert_eq!(
+            endpoint_url("https://api.example.com", &[]),
+            "https://api.example.com/"
+        );
+    }
+
+    #[test]
+    fn test_endpoint_url_segments() {
+        let segments = vec!["users".to_string(), "123".to_string()];
+        assert_eq!(
+            endpoint_url("https://api.example.com", &segments),
+            "https://api.example.com/users/123"
+        );
+    }
+
+    #[test]
+    fn test_same_origin() {
+        let base = "https://api.example.com/v1";
+        assert!(same_origin("/oauth/token", base));
+        assert!(same_origin("https://api.example.com:443/other", base));
+        assert!(!same_origin("https://api.example.com.evil.io/x", base));
+        assert!(!same_origin("http://api.example.com/x", base));
+        assert!(!same_origin("https://api.example.com:8443/x", base));
+        assert!(!same_origin("/x", "not a url"));
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        assert_eq!(
+            resolve_url("https://api.example.com/", "/oauth/token"),
+            "https://api.example.com/oauth/token"
+        );
+        assert_eq!(
+            resolve_url("https://api.example.com", "https://auth.example.com/token"),
+            "https://auth.example.com/token"
+        );
+    }
+
+    #[test]
+    fn test_apply_default_headers_skips_reserved_headers() {
+        let mut headers = HashMap::new();
+        headers.insert("Authorization".to_string(), "Bearer token".to_string());
+        headers.insert("X-Anything-Cli-Version".to_string(), "spoofed".to_string());
+
+        let config = Config {
+            base_url: "https://api.example.com".to_string(),
+            headers: Some(headers),
+            ..Default::default()
+        };
+
+        let request = apply_default_headers(
+            Client::new().get("https://api.example.com/"),
+            &config,
+            "my-api",
+        )
+        .build()
+        .unwrap();
+
+        assert_eq!(request.headers()["authorization"], "Bearer token");
+        assert_eq!(
+            request.headers()["x-anything-cli-version"],
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(
+            request.headers()["x-anything-cli-executable-name"],
+            "my-api"
+        );
+        assert_eq!(request.headers()["x-anything-cli-schema"], ">=0, <1");
+    }
+}
@@ -1,13 +1,111 @@
-use serde::Deserialize;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// Prefix of the `schema` of instruction payloads, followed by `v<version>`.
+pub const SCHEMA_PREFIX: &str = "anything-cli/";
+
+/// Newest major version of the schema this client understands. Every minor
+/// version of it is accepted: new fields are ignored, and new actions are
+/// skipped with a warning.
+pub const MAX_SCHEMA_MAJOR: u64 = 0;
+
+/// Actions this client knows how to run.
+pub const ACTIONS: &[&str] = &[
+    "ping",
+    "print",
+    "warn",
+    "success",
+    "execute",
+    "auth",
+    "prompt",
+    "confirm",
+    "select",
+    "submit",
+    "request",
+    "table",
+    "kv",
+    "markdown",
+    "progress",
+    "spinner",
+    "write_file",
+    "download",
+    "open_url",
+    "stop",
+    "none",
+];
+
+/// Schema versions this client supports, sent in the
+/// `x-anything-cli-schema` header.
+pub fn supported_schemas() -> String {
+    format!(">=0, <{}", MAX_SCHEMA_MAJOR + 1)
+}
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+/// Text content is kept as is. Structured content (e.g. the object of an
+/// `auth` instruction) is kept as JSON text, for the action to parse.
+fn content_text<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => None,
+        Some(Value::String(text)) => Some(text),
+        Some(value) => Some(value.to_string()),
+    })
+}
+
+#[derive(Debug, Deserialize, Default, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Instruction {
     pub action: String,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "content_text")]
     pub content: Option<String>,
     #[serde(default)]
     pub error: Option<bool>,
+    /// `print`: severity of the message, one of `info`, `success`, `warn`,
+    /// `error` and `debug`.
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Variable to store the output in (`execute`: stdout).
+    #[serde(default)]
+    pub capture: Option<String>,
+    /// Variable to store the exit code in (`execute`).
+    #[serde(default, rename = "capture_status")]
+    pub capture_status: Option<String>,
+    /// Condition for running the instruction, e.g. `status == 0`.
+    #[serde(default, rename = "if")]
+    pub condition: Option<String>,
+    /// Set to `false` to stop at this instruction when it fails.
+    #[serde(default, rename = "continue_on_error")]
+    pub continue_on_error: Option<bool>,
+    /// Instructions to run when this one fails.
+    #[serde(default, rename = "on_error")]
+    pub on_error: Vec<Instruction>,
+    /// Exit code of the run (`stop`).
+    #[serde(default, rename = "exit_code")]
+    pub exit_code: Option<i32>,
+    /// `execute`: program and arguments, run without a shell instead of
+    /// the `content` script.
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// `execute`: working directory, relative to the repository root with
+    /// `repo_root`.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// `execute`: run at the root of the current git repository.
+    #[serde(default, rename = "repo_root")]
+    pub repo_root: bool,
+    /// `execute`: extra environment variables.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// `execute`: text fed to the command.
+    #[serde(default)]
+    pub stdin: Option<String>,
+    /// `execute`: seconds after which the command and its children are killed.
+    #[serde(default, rename = "timeout_secs")]
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq)]
@@ -15,20 +113,212 @@ pub struct Instruction {
 pub struct AnythingSchema {
     pub schema: String,
     pub instructions: Option<Vec<Instruction>>,
+    /// Base64 Ed25519 signature, see `signing`.
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Oldest client version allowed to process the instructions.
+    #[serde(default, rename = "min_client_version")]
+    pub min_client_version: Option<String>,
+}
+
+/// Why a payload claiming to follow the schema can't be processed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    /// A version that isn't `<major>[.<minor>[.<patch>]]`.
+    InvalidVersion { field: &'static str, value: String },
+    /// A major version of the schema newer than this client supports.
+    Newer(String),
+    /// The server requires a newer client with `min_client_version`.
+    ClientTooOld(String),
+    /// A payload that doesn't parse, e.g. a field of the wrong type. `path`
+    /// is the failing field, like `instructions[2].timeout_secs`.
+    Malformed {
+        line: usize,
+        column: usize,
+        path: Option<String>,
+        message: String,
+    },
+    /// An action this client doesn't know, at `path`.
+    UnknownAction { path: String, action: String },
+    /// A body that doesn't claim to follow the schema.
+    NotAPayload,
+}
+
+impl SchemaError {
+    pub fn message(&self, executable_name: &str) -> String {
+        match self {
+            SchemaError::InvalidVersion { field, value } => {
+                format!("Invalid {} in the response: {}", field, value)
+            }
+            SchemaError::Newer(schema) => format!(
+                "The response uses {}, which this version of {} doesn't support (up to {}v{}). Please run `{} self:update`.",
+                schema, executable_name, SCHEMA_PREFIX, MAX_SCHEMA_MAJOR, executable_name
+            ),
+            SchemaError::ClientTooOld(required) => format!(
+                "The server requires {} {} or newer, this is {}. Please run `{} self:update`.",
+                executable_name,
+                required,
+                env!("CARGO_PKG_VERSION"),
+                executable_name
+            ),
+            SchemaError::Malformed {
+                line,
+                column,
+                path,
+                message,
+            } => match path {
+                Some(path) => format!(
+                    "Invalid instructions at line {}, column {}: {}: {}",
+                    line, column, path, message
+                ),
+                None => format!(
+                    "Invalid instructions at line {}, column {}: {}",
+                    line, column, message
+                ),
+            },
+            SchemaError::UnknownAction { path, action } => {
+                format!("Unknown action at {}: {}", path, action)
+            }
+            SchemaError::NotAPayload => format!(
+                "Not an instructions payload: it needs a \"schema\" starting with {} and \"instructions\"",
+                SCHEMA_PREFIX
+            ),
+        }
+    }
+}
+
+/// Numbers of a `1.2.3` version, with an optional leading `v`.
+fn parse_version(text: &str) -> Option<Vec<u64>> {
+    let text = text.trim();
+    let text = text.strip_prefix('v').unwrap_or(text);
+    text.split('.').map(|part| part.parse().ok()).collect()
 }
 
-pub fn parse_anything_schema(json_str: &str) -> Option<AnythingSchema> {
-    let parsed: AnythingSchema = match serde_json::from_str(json_str) {
-        Ok(val) => val,
-        Err(_) => {
-            return None;
+/// Whether version `a` is older than `b`, missing numbers counting as 0.
+fn is_older(a: &[u64], b: &[u64]) -> bool {
+    let length = a.len().max(b.len());
+    let padded = |version: &[u64]| {
+        let mut version = version.to_vec();
+        version.resize(length, 0);
+        version
+    };
+    padded(a) < padded(b)
+}
+
+/// Checks the version of a payload claiming to follow the schema.
+fn check_versions(schema: &str, min_client_version: Option<&str>) -> Result<(), SchemaError> {
+    let version = schema
+        .strip_prefix(SCHEMA_PREFIX)
+        .filter(|version| version.starts_with('v'))
+        .and_then(parse_version)
+        .ok_or_else(|| SchemaError::InvalidVersion {
+            field: "schema",
+            value: schema.to_string(),
+        })?;
+    if version[0] > MAX_SCHEMA_MAJOR {
+        return Err(SchemaError::Newer(schema.to_string()));
+    }
+
+    if let Some(required) = min_client_version {
+        let required_version =
+            parse_version(required).ok_or_else(|| SchemaError::InvalidVersion {
+                field: "min_client_version",
+                value: required.to_string(),
+            })?;
+        let current = parse_version(env!("CARGO_PKG_VERSION")).unwrap_or_default();
+        if is_older(&current, &required_version) {
+            return Err(SchemaError::ClientTooOld(required.to_string()));
         }
+    }
+    Ok(())
+}
+
+fn malformed(err: &serde_json::Error, path: Option<String>) -> SchemaError {
+    // The message without serde_json's " at line L column C"
+    let message = err.to_string();
+    let message = match message.rfind(" at line ") {
+        Some(end) => message[..end].to_string(),
+        None => message,
+    };
+    SchemaError::Malformed {
+        line: err.line(),
+        column: err.column(),
+        path,
+        message,
+    }
+}
+
+/// A `schema` claim, found in bodies that aren't valid JSON.
+static SCHEMA_CLAIM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r#""schema"\s*:\s*"{}"#,
+        regex::escape(SCHEMA_PREFIX)
+    ))
+    .unwrap()
+});
+
+/// Whether a body that isn't valid JSON still looks like instructions.
+fn claims_schema(text: &str) -> bool {
+    SCHEMA_CLAIM.is_match(text)
+}
+
+/// Reads the instructions of a response body: `None` when it doesn't follow
+/// the schema, to be printed as is, and an error when it claims to but this
+/// client can't process it.
+pub fn parse_anything_schema(json_str: &str) -> Result<Option<AnythingSchema>, SchemaError> {
+    let value = match serde_json::from_str::<Value>(json_str) {
+        Ok(value) => value,
+        Err(err) if claims_schema(json_str) => return Err(malformed(&err, None)),
+        Err(_) => return Ok(None),
+    };
+    let schema = match value.get("schema").and_then(Value::as_str) {
+        Some(schema) if schema.starts_with(SCHEMA_PREFIX) => schema,
+        _ => return Ok(None),
     };
+    let min_client_version = value.get("min_client_version").and_then(Value::as_str);
+    check_versions(schema, min_client_version)?;
+    if value.get("instructions").is_none() {
+        return Ok(None);
+    }
+
+    // Parsed again from the text, for the position of errors
+    let mut deserializer = serde_json::Deserializer::from_str(json_str);
+    serde_path_to_error::deserialize::<_, AnythingSchema>(&mut deserializer)
+        .map(Some)
+        .map_err(|err| {
+            let path = err.path().to_string();
+            malformed(err.inner(), (path != ".").then_some(path))
+        })
+}
 
-    if parsed.schema.starts_with("anything-cli/v0") && parsed.instructions.is_some() {
-        Some(parsed)
-    } else {
-        None
+fn unknown_actions(instructions: &[Instruction], path: &str, errors: &mut Vec<SchemaError>) {
+    for (index, instruction) in instructions.iter().enumerate() {
+        let path = format!("{}[{}]", path, index);
+        if !ACTIONS.contains(&instruction.action.as_str()) {
+            errors.push(SchemaError::UnknownAction {
+                path: format!("{}.action", path),
+                action: instruction.action.clone(),
+            });
+        }
+        unknown_actions(&instruction.on_error, &format!("{}.on_error", path), errors);
+    }
+}
+
+/// Everything wrong with a payload, for `self:validate`. Unlike when
+/// processing a response, unknown actions are reported.
+pub fn validate(text: &str) -> Vec<SchemaError> {
+    match parse_anything_schema(text) {
+        Ok(Some(parsed)) => {
+            let mut errors = Vec::new();
+            unknown_actions(
+                parsed.instructions.as_deref().unwrap_or_default(),
+                "instructions",
+                &mut errors,
+            );
+            errors
+        }
+        Ok(None) => vec![SchemaError::NotAPayload],
+        Err(err) => vec![err],
     }
 }
 
@@ -52,7 +342,7 @@ mod tests {
             ]
         }"#;
 
-        let result = parse_anything_schema(json_str);
+        let result = parse_anything_schema(json_str).unwrap();
         assert!(result.is_some());
 
         let schema = result.unwrap();
@@ -85,7 +375,7 @@ mod tests {
             ]
         }"#;
 
-        let result = parse_anything_schema(json_str);
+        let result = parse_anything_schema(json_str).unwrap();
         assert!(result.is_some());
 
         let schema = result.unwrap();
@@ -106,7 +396,7 @@ mod tests {
             ]
         }"#;
 
-        let result = parse_anything_schema(json_str);
+        let result = parse_anything_schema(json_str).unwrap();
         assert!(result.is_some());
 
         let schema = result.unwrap();
@@ -128,10 +418,100 @@ mod tests {
             ]
         }"#;
 
-        let result = parse_anything_schema(json_str);
+        let result = parse_anything_schema(json_str).unwrap();
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_schema_versions() {
+        let payload = |schema: &str| format!(r#"{{"schema": "{}", "instructions": []}}"#, schema);
+
+        assert!(parse_anything_schema(&payload("anything-cli/v0.9"))
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            parse_anything_schema(&payload("anything-cli/v1.0")),
+            Err(SchemaError::Newer("anything-cli/v1.0".to_string()))
+        );
+        assert!(matches!(
+            parse_anything_schema(&payload("anything-cli/latest")),
+            Err(SchemaError::InvalidVersion {
+                field: "schema",
+                ..
+            })
+        ));
+        let message = SchemaError::Newer("anything-cli/v1".to_string()).message("my-api");
+        assert!(message.contains("Please run `my-api self:update`"));
+    }
+
+    #[test]
+    fn test_malformed_payloads() {
+        let wrong_type = r#"{
+  "schema": "anything-cli/v0",
+  "instructions": [
+    {"action": "print"},
+    {"action": "execute", "content": "make", "timeout_secs": "5"}
+  ]
+}"#;
+        match parse_anything_schema(wrong_type) {
+            Err(SchemaError::Malformed {
+                line, column, path, ..
+            }) => {
+                assert_eq!((line, column), (5, 64));
+                assert_eq!(path.as_deref(), Some("instructions[1].timeout_secs"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let broken = r#"{"schema": "anything-cli/v0", "instructions": [{"action": "print",}]}"#;
+        assert!(matches!(
+            parse_anything_schema(broken),
+            Err(SchemaError::Malformed {
+                line: 1,
+                path: None,
+                ..
+            })
+        ));
+        assert!(parse_anything_schema(r#"{"message": "not json"#)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_validate() {
+        let payload = r#"{"schema": "anything-cli/v0", "instructions": [
+            {"action": "print"},
+            {"action": "execute", "content": "false", "on_error": [{"action": "pritn"}]}
+        ]}"#;
+        assert_eq!(
+            validate(payload),
+            vec![SchemaError::UnknownAction {
+                path: "instructions[1].on_error[0].action".to_string(),
+                action: "pritn".to_string(),
+            }]
+        );
+        assert_eq!(validate(r#"{"id": 1}"#), vec![SchemaError::NotAPayload]);
+    }
+
+    #[test]
+    fn test_min_client_version() {
+        let payload = |version: &str| {
+            format!(
+                r#"{{"schema": "anything-cli/v0", "min_client_version": "{}", "instructions": []}}"#,
+                version
+            )
+        };
+
+        assert!(parse_anything_schema(&payload("0.0.1")).is_ok());
+        assert!(parse_anything_schema(&payload(env!("CARGO_PKG_VERSION"))).is_ok());
+        assert_eq!(
+            parse_anything_schema(&payload("999.0")),
+            Err(SchemaError::ClientTooOld("999.0".to_string()))
+        );
+        assert!(parse_anything_schema(&payload("soon")).is_err());
+        assert!(is_older(&[0, 9], &[0, 10, 0]) && !is_older(&[1, 0], &[1]));
+    }
+
     #[test]
     fn test_parse_anything_schema_no_instructions() {
         let json_str = r#"
@@ -139,7 +519,7 @@ mod tests {
             "schema": "anything-cli/v0.1.0"
         }"#;
 
-        let result = parse_anything_schema(json_str);
+        let result = parse_anything_schema(json_str).unwrap();
         assert!(result.is_none());
     }
 
@@ -151,7 +531,7 @@ mod tests {
             "instructions": []
         }"#;
 
-        let result = parse_anything_schema(json_str);
+        let result = parse_anything_schema(json_str).unwrap();
         assert!(result.is_some());
 
         let schema = result.unwrap();
@@ -162,7 +542,7 @@ mod tests {
     #[test]
     fn test_parse_anything_schema_invalid_json() {
         let json_str = "{ invalid json }";
-        let result = parse_anything_schema(json_str);
+        let result = parse_anything_schema(json_str).unwrap();
         assert!(result.is_none());
     }
 
@@ -177,7 +557,7 @@ mod tests {
             ]
         }"#;
 
-        let result = parse_anything_schema(json_str);
+        let result = parse_anything_schema(json_str).unwrap();
         assert!(result.is_none());
     }
 
@@ -193,7 +573,7 @@ mod tests {
             ]
         }"#;
 
-        let schema = parse_anything_schema(json_str).unwrap();
+        let schema = parse_anything_schema(json_str).unwrap().unwrap();
         let instruction = &schema.instructions.unwrap()[0];
 
         assert_eq!(instruction.action, "test");
@@ -201,6 +581,45 @@ mod tests {
         assert!(instruction.error.is_none());
     }
 
+    #[test]
+    fn test_instruction_field_names() {
+        let instruction: Instruction = serde_json::from_str(
+            r#"{"action": "execute", "command": ["true"], "repo_root": true,
+                "timeout_secs": 5, "capture_status": "code", "continue_on_error": false,
+                "on_error": [{"action": "stop", "exit_code": 2}]}"#,
+        )
+        .unwrap();
+
+        assert!(instruction.repo_root);
+        assert_eq!(instruction.timeout_secs, Some(5));
+        assert_eq!(instruction.capture_status.as_deref(), Some("code"));
+        assert_eq!(instruction.continue_on_error, Some(false));
+        assert_eq!(instruction.on_error[0].exit_code, Some(2));
+    }
+
+    #[test]
+    fn test_instruction_structured_content() {
+        let json_str = r#"
+        {
+            "schema": "anything-cli/v0",
+            "instructions": [
+                {"action": "auth", "content": {"endpoint": "login"}},
+                {"action": "print", "content": null}
+            ]
+        }"#;
+
+        let instructions = parse_anything_schema(json_str)
+            .unwrap()
+            .unwrap()
+            .instructions
+            .unwrap();
+        assert_eq!(
+            instructions[0].content.as_deref(),
+            Some(r#"{"endpoint":"login"}"#)
+        );
+        assert!(instructions[1].content.is_none());
+    }
+
     #[test]
     fn test_parse_complex_schema() {
         let json_str = r#"
@@ -227,7 +646,7 @@ mod tests {
             ]
         }"#;
 
-        let result = parse_anything_schema(json_str);
+        let result = parse_anything_schema(json_str).unwrap();
         assert!(result.is_some());
 
         let schema = result.unwrap();
@@ -0,0 +1,279 @@
+use crate::config::data::{Config, DEFAULT_PROFILE};
+use crate::config::saver::write_private;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Header value prefix for values kept in the encrypted store.
+pub const SECRET_PREFIX: &str = "secret:";
+/// Header value prefix for values printed by a command, e.g. `cmd:pass show api-token`.
+pub const COMMAND_PREFIX: &str = "cmd:";
+
+const STORE_FILE: &str = "secrets.enc";
+const KEY_FILE: &str = "secrets.key";
+const NONCE_LEN: usize = 12;
+
+/// Secrets encrypted with ChaCha20-Poly1305. The key lives in its own 0600
+/// file next to the store, so a copied or shared `config.json` never carries
+/// credentials.
+pub struct SecretStore {
+    path: PathBuf,
+    key_path: PathBuf,
+    entries: BTreeMap<String, String>,
+}
+
+fn sibling(config_path: &Path, name: &str) -> PathBuf {
+    config_path
+        .parent()
+        .map(|dir| dir.join(name))
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+impl SecretStore {
+    pub fn open(config_path: &Path) -> Result<SecretStore, String> {
+        let mut store = SecretStore {
+            path: sibling(config_path, STORE_FILE),
+            key_path: sibling(config_path, KEY_FILE),
+            entries: BTreeMap::new(),
+        };
+
+        let data = match fs::read(&store.path) {
+            Ok(data) => data,
+            Err(_) => return Ok(store),
+        };
+        if data.len() < NONCE_LEN {
+            return Err(format!("Corrupted secret store: {:?}", store.path));
+        }
+
+        let cipher = store.cipher(false)?;
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| format!("Failed to decrypt the secret store {:?}", store.path))?;
+        store.entries = serde_json::from_slice(&plaintext)
+            .map_err(|e| format!("Corrupted secret store: {}", e))?;
+
+        Ok(store)
+    }
+
+    fn cipher(&self, create: bool) -> Result<ChaCha20Poly1305, String> {
+        match fs::read(&self.key_path) {
+            Ok(key) if key.len() == 32 => Ok(ChaCha20Poly1305::new(Key::from_slice(&key))),
+            Ok(_) => Err(format!("Invalid secret key file: {:?}", self.key_path)),
+            Err(_) if create => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                if let Some(parent) = self.key_path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                write_private(&self.key_path, &key)
+                    .map_err(|e| format!("Failed to write secret key: {}", e))?;
+                Ok(ChaCha20Poly1305::new(&key))
+            }
+            Err(_) => Err(format!("Missing secret key file: {:?}", self.key_path)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(String::as_str)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.entries.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let cipher = self.cipher(true)?;
+        let plaintext = serde_json::to_vec(&self.entries).map_err(|e| e.to_string())?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| "Failed to encrypt the secret store".to_string())?;
+
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+        write_private(&self.path, &data).map_err(|e| format!("Failed to write secrets: {}", e))
+    }
+}
+
+/// The encrypted store and its key, for removal on uninstall.
+pub fn store_files(config_path: &Path) -> [PathBuf; 2] {
+    [
+        sibling(config_path, STORE_FILE),
+        sibling(config_path, KEY_FILE),
+    ]
+}
+
+/// Name a header's secret is stored under: `authorization`, or
+/// `prod.authorization` for a named profile.
+pub fn secret_name(profile: &str, header: &str) -> String {
+    let header = header.to_lowercase();
+    if profile == DEFAULT_PROFILE {
+        header
+    } else {
+        format!("{}.{}", profile, header)
+    }
+}
+
+fn run_command(command: &str) -> Result<String, String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).output()
+    } else {
+        Command::new("sh").args(["-c", command]).output()
+    }
+    .map_err(|e| format!("Failed to run '{}': {}", command, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "'{}' failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches(['\r', '\n'])
+        .to_string())
+}
+
+/// Replaces `secret:` and `cmd:` header values with what they refer to. The
+/// store is only opened when a header needs it.
+pub fn resolve_header_values(config: &mut Config, config_path: &Path) -> Result<(), String> {
+    let Some(headers) = config.headers.as_mut() else {
+        return Ok(());
+    };
+
+    let mut store: Option<SecretStore> = None;
+    for (key, value) in headers.iter_mut() {
+        if let Some(name) = value.strip_prefix(SECRET_PREFIX) {
+            if store.is_none() {
+                store = Some(SecretStore::open(config_path)?);
+            }
+            let secret = store
+                .as_ref()
+                .and_then(|store| store.get(name))
+                .ok_or_else(|| format!("Secret '{}' for header {} is not set", name, key))?;
+            *value = secret.to_string();
+        } else if let Some(command) = value.strip_prefix(COMMAND_PREFIX) {
+            *value = run_command(command.trim())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_secret_store_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let mut store = SecretStore::open(&config_path).unwrap();
+        assert!(store.get("authorization").is_none());
+        store.set("authorization", "Bearer abc");
+        store.save().unwrap();
+
+        let raw = fs::read(temp_dir.path().join(STORE_FILE)).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("Bearer abc"));
+
+        let mut store = SecretStore::open(&config_path).unwrap();
+        assert_eq!(store.get("authorization"), Some("Bearer abc"));
+        assert!(store.remove("authorization"));
+        assert!(!store.remove("authorization"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_secret_store_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        let mut store = SecretStore::open(&config_path).unwrap();
+        store.set("token", "abc");
+        store.save().unwrap();
+
+        for file in [STORE_FILE, KEY_FILE] {
+            let mode = fs::metadata(temp_dir.path().join(file))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_secret_store_wrong_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        let mut store = SecretStore::open(&config_path).unwrap();
+        store.set("token", "abc");
+        store.save().unwrap();
+
+        fs::write(temp_dir.path().join(KEY_FILE), [7u8; 32]).unwrap();
+        assert!(SecretStore::open(&config_path).is_err());
+    }
+
+    #[test]
+    fn test_secret_name() {
+        assert_eq!(secret_name("default", "Authorization"), "authorization");
+        assert_eq!(secret_name("prod", "X-API-Key"), "prod.x-api-key");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_header_values() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        let mut store = SecretStore::open(&config_path).unwrap();
+        store.set("authorization", "Bearer abc");
+        store.save().unwrap();
+
+        let mut headers = HashMap::new();
+        headers.insert(
+            "Authorization".to_string(),
+            "secret:authorization".to_string(),
+        );
+        headers.insert("X-Token".to_string(), "cmd:echo from-command".to_string());
+        headers.insert("Accept".to_string(), "application/json".to_string());
+        let mut config = Config {
+            headers: Some(headers),
+            ..Default::default()
+        };
+
+        resolve_header_values(&mut config, &config_path).unwrap();
+        let headers = config.headers.unwrap();
+        assert_eq!(headers["Authorization"], "Bearer abc");
+        assert_eq!(headers["X-Token"], "from-command");
+        assert_eq!(headers["Accept"], "application/json");
+
+        let mut config = Config {
+            headers: Some(HashMap::from([(
+                "X-Key".to_string(),
+                "secret:missing".to_string(),
+            )])),
+            ..Default::default()
+        };
+        assert!(resolve_header_values(&mut config, &config_path).is_err());
+
+        let mut config = Config {
+            headers: Some(HashMap::from([(
+                "X-Key".to_string(),
+                "cmd:exit 3".to_string(),
+            )])),
+            ..Default::default()
+        };
+        assert!(resolve_header_values(&mut config, &config_path).is_err());
+    }
+}
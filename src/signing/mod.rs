@@ -0,0 +1,185 @@
+//! Ed25519 signatures of instruction payloads, checked against the public key
+//! pinned with `self:trust-key`.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde_json::Value;
+
+/// Request header with the nonce that signed instructions answering the
+/// request must carry.
+pub const NONCE_HEADER: &str = "x-anything-cli-nonce";
+
+/// A random nonce for a request, so that signed instructions can't be
+/// replayed in answer to another one.
+pub fn new_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    STANDARD.encode(bytes)
+}
+
+/// Reads a base64-encoded raw Ed25519 public key (32 bytes).
+pub fn parse_public_key(text: &str) -> Result<VerifyingKey, String> {
+    let bytes = STANDARD
+        .decode(text.trim())
+        .map_err(|e| format!("Invalid public key: {}", e))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "Invalid public key: expected 32 bytes".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Invalid public key: {}", e))
+}
+
+/// Writes compact JSON with the keys of every object sorted by code point,
+/// whatever order `serde_json::Map` keeps them in.
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<_> = object.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (index, (key, item)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(item, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// What gets signed: the payload without its `signature`, as compact JSON
+/// with sorted keys and unescaped UTF-8.
+pub fn signed_message(payload: &Value) -> String {
+    let mut payload = payload.clone();
+    if let Value::Object(object) = &mut payload {
+        object.remove("signature");
+    }
+    let mut message = String::new();
+    write_canonical(&payload, &mut message);
+    message
+}
+
+/// Checks the `signature` of a response body against `public_key`, and that
+/// its `nonce` is the one sent with the request.
+pub fn verify_payload(text: &str, public_key: &str, nonce: Option<&str>) -> Result<(), String> {
+    let key = parse_public_key(public_key)?;
+    let payload: Value =
+        serde_json::from_str(text).map_err(|e| format!("Invalid payload: {}", e))?;
+    let signature = payload
+        .get("signature")
+        .and_then(Value::as_str)
+        .ok_or("the instructions are not signed")?;
+
+    let signature = STANDARD
+        .decode(signature)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or("the signature is malformed")?;
+    key.verify_strict(signed_message(&payload).as_bytes(), &signature)
+        .map_err(|_| "the signature doesn't match the pinned key".to_string())?;
+
+    let sent = nonce.ok_or("no nonce was sent with the request")?;
+    if payload.get("nonce").and_then(Value::as_str) != Some(sent) {
+        return Err("the instructions don't answer this request (nonce mismatch)".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use serde_json::json;
+
+    fn sign(payload: &Value, key: &SigningKey) -> String {
+        let mut signed = payload.clone();
+        let signature = key.sign(signed_message(payload).as_bytes());
+        signed["signature"] = Value::String(STANDARD.encode(signature.to_bytes()));
+        signed.to_string()
+    }
+
+    #[test]
+    fn test_signed_message_is_canonical() {
+        let payload: Value = serde_json::from_str(
+            r#"{ "schema": "anything-cli/v0", "instructions": [], "signature": "x" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            signed_message(&payload),
+            r#"{"instructions":[],"schema":"anything-cli/v0"}"#
+        );
+
+        let nested = json!({"b": {"z": 1, "a": "é\n"}, "a": [{"y": 2.5, "x": null}]});
+        assert_eq!(
+            signed_message(&nested),
+            r#"{"a":[{"x":null,"y":2.5}],"b":{"a":"é\n","z":1}}"#
+        );
+    }
+
+    #[test]
+    fn test_verify_payload() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = STANDARD.encode(key.verifying_key().to_bytes());
+        let payload = json!({
+            "schema": "anything-cli/v0",
+            "nonce": "n1",
+            "instructions": [{"action": "execute", "content": "git pull"}]
+        });
+
+        let signed = sign(&payload, &key);
+        assert!(verify_payload(&signed, &public_key, Some("n1")).is_ok());
+
+        let tampered = signed.replace("git pull", "rm -rf ~");
+        assert_eq!(
+            verify_payload(&tampered, &public_key, Some("n1")).unwrap_err(),
+            "the signature doesn't match the pinned key"
+        );
+        assert_eq!(
+            verify_payload(&payload.to_string(), &public_key, Some("n1")).unwrap_err(),
+            "the instructions are not signed"
+        );
+
+        let other = SigningKey::from_bytes(&[8; 32]);
+        assert!(verify_payload(&sign(&payload, &other), &public_key, Some("n1")).is_err());
+        assert!(parse_public_key("not a key").is_err());
+    }
+
+    #[test]
+    fn test_replayed_payload_refused() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = STANDARD.encode(key.verifying_key().to_bytes());
+        let signed = sign(
+            &json!({"schema": "anything-cli/v0", "nonce": "n1", "instructions": []}),
+            &key,
+        );
+
+        assert_eq!(
+            verify_payload(&signed, &public_key, Some("n2")).unwrap_err(),
+            "the instructions don't answer this request (nonce mismatch)"
+        );
+        assert!(verify_payload(&signed, &public_key, None).is_err());
+
+        let unbound = sign(
+            &json!({"schema": "anything-cli/v0", "instructions": []}),
+            &key,
+        );
+        assert!(verify_payload(&unbound, &public_key, Some("n1")).is_err());
+        assert_ne!(new_nonce(), new_nonce());
+    }
+}
@@ -7,6 +7,22 @@ pub fn get_executable_name() -> String {
         .unwrap_or_else(|| "default".to_string())
 }
 
+/// Name of an environment variable scoped to the executable, e.g.
+/// `MY_API_PROFILE` for `my-api` and `PROFILE`.
+pub fn env_var_name(executable_name: &str, suffix: &str) -> String {
+    let prefix: String = executable_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}_{}", prefix, suffix)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -20,6 +36,12 @@ mod tests {
         assert!(!name.contains('\\'));
     }
 
+    #[test]
+    fn test_env_var_name() {
+        assert_eq!(env_var_name("my-api", "PROFILE"), "MY_API_PROFILE");
+        assert_eq!(env_var_name("api.v2", "BASE_URL"), "API_V2_BASE_URL");
+    }
+
     #[test]
     fn test_get_executable_name_consistency() {
         // Should return the same value when called multiple times
@@ -35,6 +57,6 @@ mod tests {
         // Usually in tests this would be something like "deps" or the test runner name
         // We just check it's not empty and reasonable
         assert!(!name.is_empty());
-        assert!(name.len() > 0);
+        assert!(!name.is_empty());
     }
 }
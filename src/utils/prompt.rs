@@ -0,0 +1,53 @@
+use std::io::{self, BufRead, Write};
+
+/// Asks for a line of input on stderr, so that prompts never end up in
+/// piped output. An empty answer gives `default`, when there is one.
+pub fn ask(label: &str, default: Option<&str>) -> Result<String, String> {
+    match default {
+        Some(default) if !default.is_empty() => eprint!("{} [{}]: ", label, default),
+        _ => eprint!("{}: ", label),
+    }
+    io::stderr().flush().map_err(|e| e.to_string())?;
+
+    let mut line = String::new();
+    let read = io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read input: {}", e))?;
+    if read == 0 {
+        return Err(format!("No input for '{}'", label));
+    }
+
+    let answer = line.trim_end_matches(['\r', '\n']).to_string();
+    Ok(match default {
+        Some(default) if answer.is_empty() => default.to_string(),
+        _ => answer,
+    })
+}
+
+/// Asks without echoing what is typed.
+pub fn ask_secret(label: &str) -> Result<String, String> {
+    rpassword::prompt_password(format!("{}: ", label))
+        .map_err(|e| format!("Failed to read input: {}", e))
+}
+
+/// Opens `url` in the default browser, returning whether that worked.
+pub fn open_in_browser(url: &str) -> bool {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+
+    command
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
@@ -7,7 +7,7 @@ use tempfile::TempDir;
 #[test]
 fn test_cli_compiles_and_runs() {
     let output = Command::new("cargo")
-        .args(&["build", "--bin", "anything-cli"])
+        .args(["build", "--bin", "anything-cli"])
         .output()
         .expect("Failed to execute cargo build command");
 
@@ -22,7 +22,7 @@ fn test_cli_compiles_and_runs() {
 #[test]
 fn test_version_flag() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "--version"])
+        .args(["run", "--", "--version"])
         .output()
         .expect("Failed to execute cargo run command");
 
@@ -35,7 +35,7 @@ fn test_version_flag() {
 #[test]
 fn test_version_flag_short() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "-v"])
+        .args(["run", "--", "-v"])
         .output()
         .expect("Failed to execute cargo run command");
 
@@ -49,7 +49,7 @@ fn test_version_flag_short() {
 fn test_argument_parsing() {
     // Test insufficient arguments for set-base-url
     let output = Command::new("cargo")
-        .args(&["run", "--", "self:set-base-url"])
+        .args(["run", "--", "self:set-base-url"])
         .output()
         .expect("Failed to execute cargo command");
 
@@ -63,7 +63,7 @@ fn test_argument_parsing() {
 #[test]
 fn test_invalid_internal_command() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "self:invalid-command"])
+        .args(["run", "--", "self:invalid-command"])
         .output()
         .expect("Failed to execute cargo command");
 
@@ -122,7 +122,7 @@ fn test_cli_argument_patterns() {
 
     for (args, description) in test_cases {
         let output = Command::new("cargo")
-            .args(&["run", "--"])
+            .args(["run", "--"])
             .args(&args)
             .output()
             .expect("Failed to execute cargo command");
@@ -142,7 +142,7 @@ fn test_cli_argument_patterns() {
 fn test_binary_exists_after_build() {
     // Build the project
     let build_output = Command::new("cargo")
-        .args(&["build"])
+        .args(["build"])
         .output()
         .expect("Failed to execute cargo build command");
 
@@ -165,7 +165,7 @@ fn test_binary_exists_after_build() {
 #[test]
 fn test_cli_no_arguments() {
     let output = Command::new("cargo")
-        .args(&["run"])
+        .args(["run"])
         .output()
         .expect("Failed to execute cargo command");
 
@@ -179,7 +179,7 @@ fn test_cli_no_arguments() {
 #[test]
 fn test_unit_tests_pass() {
     let output = Command::new("cargo")
-        .args(&["test", "--lib"])
+        .args(["test", "--lib"])
         .output()
         .expect("Failed to execute cargo test command");
 
@@ -205,7 +205,7 @@ fn test_module_integration() {
 
     // The CLI should handle the case where no config exists
     let output = Command::new("cargo")
-        .args(&["run", "--", "test-command"])
+        .args(["run", "--", "test-command"])
         .output()
         .expect("Failed to execute cargo command");
 
@@ -224,7 +224,7 @@ fn test_module_integration() {
 fn test_git_integration() {
     // Test that git detection doesn't cause the CLI to crash
     let output = Command::new("cargo")
-        .args(&["run", "--", "--version"])
+        .args(["run", "--", "--version"])
         .output()
         .expect("Failed to execute cargo run command");
 
@@ -242,7 +242,7 @@ fn test_cli_startup_performance() {
     let start = Instant::now();
 
     let output = Command::new("cargo")
-        .args(&["run", "--", "--version"])
+        .args(["run", "--", "--version"])
         .output()
         .expect("Failed to execute cargo run command");
 